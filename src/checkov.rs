@@ -0,0 +1,161 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{
+  CodeDescription, Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, Url,
+};
+use serde::Deserialize;
+
+use crate::{
+  config::Config,
+  error::PluginError,
+  host::Host,
+  log,
+  scanner::{Findings, Scanned, Scanner},
+  server::{self, host_path},
+};
+
+// checkov is a Python package without standalone builds for every platform,
+// so it isn't installed by the plugin.
+const CHECKOV: &str = "checkov";
+
+// With more than one framework checkov prints a list of reports.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Output {
+  Many(Vec<Report>),
+  One(Report),
+}
+
+#[derive(Default, Deserialize)]
+struct Report {
+  #[serde(default)]
+  results: Results,
+}
+
+#[derive(Default, Deserialize)]
+struct Results {
+  #[serde(default)]
+  failed_checks: Vec<FailedCheck>,
+}
+
+#[derive(Deserialize)]
+struct FailedCheck {
+  check_id: String,
+  check_name: String,
+  #[serde(default)]
+  file_line_range: Vec<u32>,
+  guideline: Option<String>,
+}
+
+// Runs checkov on each saved file.
+pub struct Checkov {
+  path: Option<String>,
+}
+
+impl Checkov {
+  pub fn new(config: &Config) -> Checkov {
+    Checkov {
+      path: config.checkov_path.clone(),
+    }
+  }
+}
+
+impl Scanner for Checkov {
+  fn name(&self) -> &'static str {
+    CHECKOV
+  }
+
+  fn scan(&mut self, host: &dyn Host, root_uri: &Url, saved: &Url) -> Result<(Scanned, Findings)> {
+    let binary = match &self.path {
+      | Some(path) => server::resolve_server_path(host, path, Some(root_uri))?,
+      | None => string!(CHECKOV),
+    };
+
+    let file = host_path(saved);
+    // checkov exits unsuccessfully when a check fails unless told not to.
+    let output = log::timed("checkov scan", || {
+      host.execute(
+        &binary,
+        vec![
+          string!("--file"),
+          file.clone(),
+          string!("--output"),
+          string!("json"),
+          string!("--quiet"),
+          string!("--soft-fail"),
+        ],
+      )
+    });
+    let Some(output) = output else {
+      return match server::can_execute(host, &binary) {
+        | true => Err(anyhow!("Failed to scan {file} with {binary}")),
+        | false => Err(
+          PluginError::InvalidSetting {
+            name: "volt.checkov.path",
+            value: binary,
+          }
+          .into(),
+        ),
+      };
+    };
+
+    let reports = match serde_json::from_slice(&output)
+      .map_err(|e| anyhow!("Failed to parse the output of {binary}: {e}"))?
+    {
+      | Output::Many(v) => v,
+      | Output::One(v) => vec![v],
+    };
+
+    let diagnostics: Vec<Diagnostic> = reports
+      .into_iter()
+      .flat_map(|v| v.results.failed_checks)
+      .map(translate)
+      .collect();
+    debug!(format!(
+      "checkov reported {} failed checks in {file}",
+      diagnostics.len()
+    ));
+
+    let mut findings = Findings::new();
+    findings.insert(saved.clone(), diagnostics);
+    Ok((Scanned::File(saved.clone()), findings))
+  }
+}
+
+// Line ranges are 1-based.
+fn translate(check: FailedCheck) -> Diagnostic {
+  let start = check
+    .file_line_range
+    .first()
+    .copied()
+    .unwrap_or_default()
+    .saturating_sub(1);
+  let end = check
+    .file_line_range
+    .get(1)
+    .copied()
+    .unwrap_or_default()
+    .saturating_sub(1)
+    .max(start);
+
+  Diagnostic {
+    range: Range {
+      start: Position {
+        line: start,
+        character: 0,
+      },
+      end: Position {
+        line: end,
+        character: u32::MAX,
+      },
+    },
+    severity: Some(DiagnosticSeverity::WARNING),
+    code: Some(NumberOrString::String(check.check_id)),
+    code_description: check
+      .guideline
+      .and_then(|v| Url::parse(&v).ok())
+      .map(|href| CodeDescription { href }),
+    source: Some(string!(CHECKOV)),
+    message: check.check_name,
+    ..Default::default()
+  }
+}
@@ -0,0 +1,135 @@
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{config::Config, host::Host, install::Version, server, state::PersistedState};
+
+#[derive(Deserialize)]
+struct VersionOutput {
+  // OpenTofu uses the same key.
+  terraform_version: String,
+}
+
+// The CLI the server runs, `-tf-exec` takes precedence over `terraform.path`,
+// which takes precedence over PATH.
+pub fn cli_path(config: &Config) -> String {
+  let cli = config.cli();
+  let tf_exec = config
+    .server_args
+    .iter()
+    .find_map(|v| v.strip_prefix("-tf-exec="));
+  match (tf_exec, config.server_setting(&format!("{cli}.path"))) {
+    | (Some(v), _) => string!(v),
+    | (None, Some(Value::String(v))) if !v.is_empty() => v.clone(),
+    | _ => string!(cli),
+  }
+}
+
+// `version -json` is only supported since Terraform 0.13, older releases
+// print the same as `version`, starting with `Terraform v0.12.31`.
+fn parse_version(output: &[u8]) -> Option<Version> {
+  let version = match serde_json::from_slice::<VersionOutput>(output) {
+    | Ok(v) => v.terraform_version,
+    | Err(_) => {
+      let output = String::from_utf8_lossy(output);
+      let line = output.lines().next()?;
+      let (_, version) = line.trim().rsplit_once(' ')?;
+      string!(version)
+    }
+  };
+  Version::parse(version.trim_start_matches('v'))
+}
+
+// Provider schemas, validation and formatting need the CLI, without it the
+// server only offers what it knows from the configuration itself.
+fn problem(host: &dyn Host, config: &Config) -> Option<String> {
+  let cli = config.cli();
+  let path = cli_path(config);
+  let (minimum, install_url) = match cli {
+    // Every release of OpenTofu has what tofu-ls needs.
+    | "tofu" => (None, "https://opentofu.org/docs/intro/install/"),
+    | _ => (
+      // The first release with `providers schema -json`.
+      Some("0.12.0"),
+      "https://developer.hashicorp.com/terraform/install",
+    ),
+  };
+
+  let run = |args: Vec<String>| {
+    let (program, args) = server::with_server_env(host, config, &path, args);
+    host.execute(&program, args)
+  };
+  let Some(output) =
+    run(vec![string!("version"), string!("-json")]).or_else(|| run(vec![string!("version")]))
+  else {
+    return Some(format!(
+      "`{path}` was not found, so provider completions, validation and formatting won't work. \
+       Install {cli} from {install_url} or set `{cli}.path` to where it is installed"
+    ));
+  };
+  let version = parse_version(&output)?;
+  let minimum = minimum?;
+  match Version::parse(minimum).is_some_and(|v| version < v) {
+    | true => Some(format!(
+      "`{path}` is {cli} {version}, but {} needs at least {minimum} for provider completions and \
+       validation. Install a newer release from {install_url}",
+      config.product.name
+    )),
+    | false => None,
+  }
+}
+
+// Tells about a missing or outdated CLI once, until it has been fixed.
+pub fn check(host: &dyn Host, config: &Config) -> Result<()> {
+  let cli = config.cli();
+  let problem = problem(host, config);
+  let notified = PersistedState::load(host)
+    .cli_problems_notified
+    .contains(cli);
+  match (problem, notified) {
+    | (Some(problem), false) => {
+      warn!(problem);
+      PersistedState::update(host, |v| {
+        v.cli_problems_notified.insert(string!(cli));
+      })?;
+    }
+    | (Some(problem), true) => debug!(problem),
+    | (None, true) => {
+      PersistedState::update(host, |v| {
+        v.cli_problems_notified.remove(cli);
+      })?;
+    }
+    | (None, false) => {}
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockHost;
+
+  fn problem_with(output: &'static str) -> Option<String> {
+    let mut host = MockHost::new();
+    // The CLI runs through `env` to turn off its checkpoint calls.
+    host.program("env", move |_| Some(output.as_bytes().to_vec()));
+    problem(&host, &Config::parse(None).unwrap())
+  }
+
+  #[test]
+  fn reads_the_version_of_old_and_new_releases() {
+    assert!(problem_with(r#"{"terraform_version":"1.6.0","platform":"linux_amd64"}"#).is_none());
+    assert!(
+      problem_with("Terraform v0.12.31\n\nYour version of Terraform is out of date!\n").is_none()
+    );
+    assert!(problem_with("Terraform v0.11.14\n")
+      .is_some_and(|v| v.contains("is terraform 0.11.14, but terraform-ls needs at least 0.12.0")));
+  }
+
+  #[test]
+  fn reports_a_missing_cli() {
+    let host = MockHost::new();
+    let problem = problem(&host, &Config::parse(None).unwrap()).unwrap();
+    assert!(problem.starts_with("`terraform` was not found"));
+  }
+}
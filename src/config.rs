@@ -0,0 +1,920 @@
+use std::time::Duration;
+
+use anyhow::Result;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use crate::{
+  error::PluginError,
+  ignore::IgnoreList,
+  install::{Product, DOWNLOAD_TIMEOUT_SECS, TERRAFORM_LS, TOFU_LS},
+  log::Level,
+};
+
+const MAX_RESTARTS: u32 = 5;
+const INIT_TIMEOUT_SECS: u64 = 30;
+
+// Flags of `terraform-ls serve` and `tofu-ls serve`, all of which take a
+// value. The `tf-*` ones are only known to older releases.
+const SERVE_FLAGS: &[&str] = &[
+  "cpuprofile",
+  "log-file",
+  "memprofile",
+  "port",
+  "req-concurrency",
+  "tf-exec",
+  "tf-exec-timeout",
+  "tf-log-file",
+];
+
+// Keys accepted under `volt`, has to match the fields of `VoltOptions`.
+const VOLT_OPTIONS: &[&str] = &[
+  "allowDownloads",
+  "backend",
+  "caBundlePath",
+  "channel",
+  "checkov",
+  "commandPrefix",
+  "debugPort",
+  "diagnostics",
+  "downloadSource",
+  "downloadTimeout",
+  "downloadUrlTemplate",
+  "env",
+  "hcl",
+  "infracost",
+  "initTimeout",
+  "keepVersions",
+  "localArchive",
+  "maxRestarts",
+  "pluginLogLevel",
+  "prefillRequiredFields",
+  "releasesHost",
+  "securityScanner",
+  "serverArgs",
+  "serverLogFile",
+  "serverPath",
+  "serverPerFolder",
+  "strictConfig",
+  "telemetry",
+  "terraformlsSha256",
+  "terraformlsVersion",
+  "tofulsVersion",
+  "trivyPath",
+  "updateNotifications",
+  "validation",
+];
+
+// Keys accepted in the nested objects under `volt`, have to match the fields
+// of their option structs.
+const NESTED_VOLT_OPTIONS: &[(&str, &[&str])] = &[
+  ("checkov", &["enable", "path"]),
+  ("diagnostics", &["ignore"]),
+  ("hcl", &["patterns", "mode"]),
+  ("infracost", &["path"]),
+  ("validation", &["mode"]),
+];
+
+#[derive(Default, Deserialize)]
+struct PluginConfig {
+  volt: Option<Value>,
+  #[serde(rename = "terraform-ls")]
+  terraform_ls: Option<Value>,
+  // The language server settings in volt.toml, like `terraform.path`, are
+  // set at the top level.
+  #[serde(flatten)]
+  server: Map<String, Value>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct VoltOptions {
+  #[serde(default)]
+  env: Map<String, Value>,
+  #[serde(default)]
+  server_args: Vec<String>,
+  server_path: Option<String>,
+  server_log_file: Option<String>,
+  debug_port: Option<u16>,
+  backend: Option<String>,
+  terraformls_version: Option<String>,
+  terraformls_sha256: Option<String>,
+  tofuls_version: Option<String>,
+  channel: Option<String>,
+  releases_host: Option<String>,
+  download_url_template: Option<String>,
+  download_source: Option<String>,
+  download_timeout: Option<u64>,
+  local_archive: Option<String>,
+  ca_bundle_path: Option<String>,
+  #[serde(default)]
+  allow_downloads: bool,
+  init_timeout: Option<u64>,
+  keep_versions: Option<usize>,
+  max_restarts: Option<u32>,
+  plugin_log_level: Option<String>,
+  #[serde(default)]
+  telemetry: bool,
+  #[serde(default)]
+  server_per_folder: bool,
+  update_notifications: Option<bool>,
+  security_scanner: Option<String>,
+  trivy_path: Option<String>,
+  #[serde(default)]
+  checkov: CheckovOptions,
+  #[serde(default)]
+  diagnostics: DiagnosticsOptions,
+  #[serde(default)]
+  validation: ValidationOptions,
+  #[serde(default)]
+  hcl: HclOptions,
+  #[serde(default)]
+  infracost: InfracostOptions,
+  command_prefix: Option<String>,
+  prefill_required_fields: Option<bool>,
+  // Fails on unknown keys instead of only warning about them, read before
+  // deserializing as it decides which keys are passed on.
+  #[allow(dead_code)]
+  #[serde(default)]
+  strict_config: bool,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CheckovOptions {
+  #[serde(default)]
+  enable: bool,
+  path: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InfracostOptions {
+  path: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DiagnosticsOptions {
+  #[serde(default)]
+  ignore: Vec<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ValidationOptions {
+  mode: Option<String>,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct HclOptions {
+  #[serde(default)]
+  patterns: Vec<String>,
+  mode: Option<String>,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum Channel {
+  Stable,
+  Prerelease,
+}
+
+#[derive(Clone)]
+pub enum DownloadSource {
+  Releases,
+  Github,
+}
+
+#[derive(Clone, PartialEq, Eq)]
+pub enum SecurityScanner {
+  Trivy,
+}
+
+// How HCL files that aren't Terraform files are handled.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum HclMode {
+  // Checked for syntax errors by the plugin on save.
+  Syntax,
+  // Sent to the language server, which only works for files close enough to
+  // Terraform.
+  Server,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ValidationMode {
+  Change,
+  Save,
+  Manual,
+}
+
+// Sets `section.key` unless it was set explicitly.
+fn default_option(options: &mut Map<String, Value>, section: &str, key: &str, value: Value) {
+  let section = options
+    .entry(string!(section))
+    .or_insert_with(|| Value::Object(Map::new()));
+  if let Value::Object(section) = section {
+    section.entry(string!(key)).or_insert(value);
+  }
+}
+
+// On change is the server's enhanced validation, on save is `terraform
+// validate` run by the server, on demand leaves both to
+// `terraform/validateWorkspace`.
+fn validation_options(options: Option<Value>, mode: ValidationMode) -> Value {
+  let mut options = match options {
+    | Some(Value::Object(v)) => v,
+    | _ => Map::new(),
+  };
+  default_option(
+    &mut options,
+    "validation",
+    "enableEnhancedValidation",
+    Value::Bool(mode == ValidationMode::Change),
+  );
+  default_option(
+    &mut options,
+    "experimentalFeatures",
+    "validateOnSave",
+    Value::Bool(mode == ValidationMode::Save),
+  );
+  Value::Object(options)
+}
+
+// `commandPrefix` and `experimentalFeatures.prefillRequiredFields` as set at
+// the top level or under `volt`, unless they were set under `terraform-ls`
+// already.
+fn feature_options(
+  options: Option<Value>,
+  command_prefix: Option<String>,
+  prefill_required_fields: Option<bool>,
+) -> Option<Value> {
+  if command_prefix.is_none() && prefill_required_fields.is_none() {
+    return options;
+  }
+  let mut options = match options {
+    | Some(Value::Object(v)) => v,
+    | _ => Map::new(),
+  };
+  if let Some(prefix) = command_prefix {
+    options
+      .entry(string!("commandPrefix"))
+      .or_insert(Value::String(prefix));
+  }
+  if let Some(prefill) = prefill_required_fields {
+    default_option(
+      &mut options,
+      "experimentalFeatures",
+      "prefillRequiredFields",
+      Value::Bool(prefill),
+    );
+  }
+  Some(Value::Object(options))
+}
+
+// The CLI path the settings UI sets at the top level, unless it was set under
+// `terraform-ls` already, which is where the server reads it from.
+fn cli_path_option(options: Option<Value>, cli: &str, path: Option<String>) -> Option<Value> {
+  let Some(path) = path else {
+    return options;
+  };
+  let mut options = match options {
+    | Some(Value::Object(v)) => v,
+    | _ => Map::new(),
+  };
+  default_option(&mut options, cli, "path", Value::String(path));
+  Some(Value::Object(options))
+}
+
+// A top-level setting by its dotted path, like `terraform.path`.
+fn top_level_setting<'a>(settings: &'a Map<String, Value>, path: &str) -> Option<&'a Value> {
+  let mut keys = path.split('.');
+  let first = settings.get(keys.next()?)?;
+  keys.try_fold(first, |v, key| v.get(key))
+}
+
+// The CLI the language server runs for `product`.
+fn cli_name(product: &Product) -> &'static str {
+  match product.name {
+    | "tofu-ls" => "tofu",
+    | _ => "terraform",
+  }
+}
+
+#[derive(Clone)]
+pub struct Config {
+  pub product: &'static Product,
+  // The version setting of the selected backend.
+  pub version: Option<String>,
+  // SHA256 the downloaded archive has to have, lowercase.
+  pub sha256: Option<String>,
+  pub channel: Channel,
+  pub releases_host: Option<String>,
+  pub download_url_template: Option<String>,
+  pub download_source: DownloadSource,
+  pub download_timeout: Option<Duration>,
+  pub local_archive: Option<String>,
+  // How many installed versions to keep, including the one in use.
+  pub keep_versions: usize,
+  // Certificates to trust for downloads, on top of the system ones.
+  pub ca_bundle_path: Option<String>,
+  // Downloads without asking first, for setups nobody is there to answer.
+  pub allow_downloads: bool,
+  pub server_path: Option<String>,
+  pub server_args: Vec<String>,
+  // Passed as `-log-file`, may contain `{{.Timestamp}}`, `{{.Pid}}` and
+  // `{{.Ppid}}`.
+  pub server_log_file: Option<String>,
+  pub debug_port: Option<u16>,
+  // Starts a server for every workspace folder instead of one for all.
+  pub server_per_folder: bool,
+  pub env: Vec<(String, String)>,
+  // How long the server has to keep running after starting to count as up.
+  pub init_timeout: Option<Duration>,
+  // How often the server is restarted after exiting, 0 disables restarting.
+  pub max_restarts: u32,
+  pub plugin_log_level: Level,
+  pub telemetry: bool,
+  // Whether to tell about newer releases than the installed one.
+  pub update_notifications: bool,
+  pub security_scanner: Option<SecurityScanner>,
+  pub trivy_path: Option<String>,
+  pub checkov: bool,
+  pub checkov_path: Option<String>,
+  pub infracost_path: Option<String>,
+  pub diagnostics_ignore: IgnoreList,
+  // Paths of other HCL files to handle, empty disables the HCL support.
+  pub hcl_patterns: Vec<String>,
+  pub hcl_mode: HclMode,
+  // Passed through to the language server as its initialization options.
+  pub lsp_options: Option<Value>,
+}
+
+// Empty strings count as unset, the same as a missing key.
+fn non_empty(value: Option<String>) -> Option<String> {
+  let value = value?;
+  let value = value.trim();
+  if value.is_empty() {
+    return None;
+  }
+  Some(string!(value))
+}
+
+fn invalid(name: &'static str, value: &str) -> anyhow::Error {
+  PluginError::InvalidSetting {
+    name,
+    value: string!(value),
+  }
+  .into()
+}
+
+// Turns `volt.serverArgs` into `-flag=value` arguments, whether given like
+// that, as `--flag value` in one string or as separate `-flag` and `value`.
+// `serve` is always added by the plugin, so it's dropped here. Unknown flags
+// are passed on, they may be new.
+fn normalize_server_args(args: Vec<String>) -> Result<Vec<String>> {
+  let mut normalized = vec![];
+  let mut unknown = vec![];
+  let mut args = args
+    .iter()
+    .map(|v| v.trim())
+    .filter(|v| !v.is_empty())
+    .peekable();
+
+  while let Some(arg) = args.next() {
+    if arg == "serve" {
+      continue;
+    }
+    let Some(flag) = arg.strip_prefix("--").or_else(|| arg.strip_prefix('-')) else {
+      return Err(invalid("volt.serverArgs", arg));
+    };
+    let (name, value) = match flag.split_once(['=', ' ']) {
+      | Some((name, value)) => (name, Some(string!(value.trim()))),
+      | None => (flag, None),
+    };
+    if !SERVE_FLAGS.contains(&name) {
+      unknown.push(format!("-{name}"));
+    }
+    let value = match value {
+      | Some(v) => v,
+      | None => match args.next_if(|v| !v.starts_with('-')) {
+        | Some(v) => string!(v),
+        | None => {
+          normalized.push(format!("-{name}"));
+          continue;
+        }
+      },
+    };
+    normalized.push(format!("-{name}={value}"));
+  }
+
+  if !unknown.is_empty() {
+    warn!(format!(
+      "Unknown flags in `volt.serverArgs`: {}, known flags are -{}",
+      unknown.join(", "),
+      SERVE_FLAGS.join(", -")
+    ));
+  }
+  Ok(normalized)
+}
+
+// Warns about keys of `object` that aren't `known`, and drops them unless
+// `strict`, so deserializing fails on them.
+fn drop_unknown_keys(
+  object: &mut Map<String, Value>,
+  prefix: &str,
+  known: &[&str],
+  strict: bool,
+) -> Result<()> {
+  let unknown: Vec<String> = object
+    .keys()
+    .filter(|k| !known.contains(&k.as_str()))
+    .map(|v| format!("`{prefix}.{v}`"))
+    .collect();
+  if unknown.is_empty() {
+    return Ok(());
+  }
+  warn!(format!(
+    "Unknown settings {}, valid settings are: {}",
+    unknown.join(", "),
+    known.join(", ")
+  ));
+  if !strict {
+    object.retain(|k, _| known.contains(&k.as_str()));
+  }
+  Ok(())
+}
+
+fn volt_options(mut volt: Value) -> Result<VoltOptions> {
+  let strict = volt
+    .get("strictConfig")
+    .and_then(|v| v.as_bool())
+    .unwrap_or_default();
+
+  if let Some(volt) = volt.as_object_mut() {
+    drop_unknown_keys(volt, "volt", VOLT_OPTIONS, strict)?;
+    for (key, known) in NESTED_VOLT_OPTIONS {
+      if let Some(Value::Object(nested)) = volt.get_mut(*key) {
+        drop_unknown_keys(nested, &format!("volt.{key}"), known, strict)?;
+      }
+    }
+  }
+
+  serde_json::from_value(volt).map_err(|e| {
+    PluginError::InvalidConfig {
+      reason: e.to_string(),
+    }
+    .into()
+  })
+}
+
+impl Config {
+  pub fn parse(options: Option<&Value>) -> Result<Config> {
+    let plugin: PluginConfig = match options {
+      | Some(v) => serde_json::from_value(v.clone()).map_err(|e| PluginError::InvalidConfig {
+        reason: e.to_string(),
+      })?,
+      | None => PluginConfig::default(),
+    };
+    let volt = match plugin.volt {
+      | Some(v) => volt_options(v)?,
+      | None => VoltOptions::default(),
+    };
+
+    let mut env = vec![];
+    for (key, value) in volt.env {
+      let value = match value {
+        | Value::String(v) => v,
+        | Value::Null => continue,
+        | v => v.to_string(),
+      };
+      if key.is_empty() || key.contains('=') {
+        error!(format!("Ignoring invalid environment variable name: {key}"));
+        continue;
+      }
+      env.push((key, value));
+    }
+
+    let product = match volt.backend.as_deref().map(str::trim) {
+      | None | Some("" | "terraform-ls") => &TERRAFORM_LS,
+      | Some("opentofu-ls" | "tofu-ls") => &TOFU_LS,
+      | Some(v) => return Err(invalid("volt.backend", v)),
+    };
+
+    let version = match product.name {
+      | "tofu-ls" => volt.tofuls_version,
+      | _ => volt.terraformls_version,
+    };
+
+    // Pins the terraform-ls artifact only, other backends aren't pinned.
+    let sha256 = match non_empty(volt.terraformls_sha256) {
+      | Some(_) if product.name != TERRAFORM_LS.name => {
+        warn!(format!(
+          "`volt.terraformlsSha256` only pins terraform-ls, it is ignored for {}",
+          product.name
+        ));
+        None
+      }
+      | Some(v) if v.len() == 64 && v.chars().all(|v| v.is_ascii_hexdigit()) => {
+        Some(v.to_ascii_lowercase())
+      }
+      | Some(v) => return Err(invalid("volt.terraformlsSha256", &v)),
+      | None => None,
+    };
+
+    let channel = match volt.channel.as_deref().map(str::trim) {
+      | None | Some("" | "stable") => Channel::Stable,
+      | Some("prerelease") => Channel::Prerelease,
+      | Some(v) => return Err(invalid("volt.channel", v)),
+    };
+
+    let download_source = match volt.download_source.as_deref().map(str::trim) {
+      | None | Some("" | "releases") => DownloadSource::Releases,
+      | Some("github") => DownloadSource::Github,
+      | Some(v) => return Err(invalid("volt.downloadSource", v)),
+    };
+
+    let download_timeout = match volt.download_timeout {
+      | Some(0) => None,
+      | Some(v) => Some(Duration::from_secs(v)),
+      | None => Some(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS)),
+    };
+
+    let init_timeout = match volt.init_timeout {
+      | Some(0) => None,
+      | Some(v) => Some(Duration::from_secs(v)),
+      | None => Some(Duration::from_secs(INIT_TIMEOUT_SECS)),
+    };
+
+    let plugin_log_level = match non_empty(volt.plugin_log_level) {
+      | None => Level::Info,
+      | Some(v) => Level::parse(&v).ok_or_else(|| invalid("volt.pluginLogLevel", &v))?,
+    };
+
+    let security_scanner = match volt.security_scanner.as_deref().map(str::trim) {
+      | None | Some("" | "off") => None,
+      | Some("trivy") => Some(SecurityScanner::Trivy),
+      | Some(v) => return Err(invalid("volt.securityScanner", v)),
+    };
+
+    let validation_mode = match volt.validation.mode.as_deref().map(str::trim) {
+      | None | Some("") => None,
+      | Some("onChange") => Some(ValidationMode::Change),
+      | Some("onSave") => Some(ValidationMode::Save),
+      | Some("onDemand") => Some(ValidationMode::Manual),
+      | Some(v) => return Err(invalid("volt.validation.mode", v)),
+    };
+
+    let hcl_mode = match volt.hcl.mode.as_deref().map(str::trim) {
+      | None | Some("" | "syntax") => HclMode::Syntax,
+      | Some("server") => HclMode::Server,
+      | Some(v) => return Err(invalid("volt.hcl.mode", v)),
+    };
+
+    let cli = cli_name(product);
+    let cli_path = top_level_setting(&plugin.server, &format!("{cli}.path"))
+      .and_then(|v| v.as_str())
+      .map(String::from);
+    // Both are always sent, empty and off unless changed in the settings UI.
+    let command_prefix = top_level_setting(&plugin.server, "commandPrefix")
+      .and_then(|v| v.as_str())
+      .map(String::from);
+    let prefill_required_fields =
+      top_level_setting(&plugin.server, "experimentalFeatures.prefillRequiredFields")
+        .and_then(|v| v.as_bool());
+
+    let mut server_args = vec![string!("serve")];
+    server_args.extend(normalize_server_args(volt.server_args)?);
+
+    Ok(Config {
+      product,
+      version: non_empty(version),
+      sha256,
+      channel,
+      releases_host: non_empty(volt.releases_host),
+      download_url_template: non_empty(volt.download_url_template),
+      download_source,
+      download_timeout,
+      local_archive: non_empty(volt.local_archive),
+      // The version in use is always kept.
+      keep_versions: volt.keep_versions.unwrap_or(1).max(1),
+      ca_bundle_path: non_empty(volt.ca_bundle_path),
+      allow_downloads: volt.allow_downloads,
+      server_path: non_empty(volt.server_path),
+      server_args,
+      server_log_file: non_empty(volt.server_log_file),
+      debug_port: volt.debug_port.filter(|v| *v != 0),
+      server_per_folder: volt.server_per_folder,
+      env,
+      init_timeout,
+      max_restarts: volt.max_restarts.unwrap_or(MAX_RESTARTS),
+      plugin_log_level,
+      telemetry: volt.telemetry,
+      update_notifications: volt.update_notifications.unwrap_or(true),
+      security_scanner,
+      trivy_path: non_empty(volt.trivy_path),
+      checkov: volt.checkov.enable,
+      checkov_path: non_empty(volt.checkov.path),
+      infracost_path: non_empty(volt.infracost.path),
+      diagnostics_ignore: IgnoreList::new(
+        volt
+          .diagnostics
+          .ignore
+          .into_iter()
+          .filter_map(|v| non_empty(Some(v)))
+          .collect(),
+      ),
+      hcl_patterns: volt
+        .hcl
+        .patterns
+        .into_iter()
+        .filter_map(|v| non_empty(Some(v)))
+        .collect(),
+      hcl_mode,
+      lsp_options: cli_path_option(
+        feature_options(
+          match validation_mode {
+            | Some(mode) => Some(validation_options(plugin.terraform_ls, mode)),
+            | None => plugin.terraform_ls,
+          },
+          non_empty(command_prefix).or(non_empty(volt.command_prefix)),
+          match prefill_required_fields {
+            | Some(true) => Some(true),
+            | _ => volt.prefill_required_fields,
+          },
+        ),
+        cli,
+        non_empty(cli_path),
+      ),
+    })
+  }
+
+  // For the plugin log, leaves out values of `volt.env` as they may hold
+  // credentials.
+  pub fn describe(&self) -> String {
+    let env: Vec<&str> = self.env.iter().map(|(k, _)| k.as_str()).collect();
+    format!(
+      "Config: backend {}, version {}, releases host {}, download URL template {}, local \
+       archive {}, CA bundle {}, server path {}, server args {:?}, env {env:?}, download timeout \
+       {}s, init timeout {}s, max restarts {}",
+      self.product.name,
+      self.version.as_deref().unwrap_or("default"),
+      self.releases_host(),
+      self.download_url_template(),
+      self.local_archive.as_deref().unwrap_or("none"),
+      self.ca_bundle_path.as_deref().unwrap_or("none"),
+      self.server_path.as_deref().unwrap_or("none"),
+      self.server_args,
+      self
+        .download_timeout
+        .map(|v| v.as_secs())
+        .unwrap_or_default(),
+      self.init_timeout.map(|v| v.as_secs()).unwrap_or_default(),
+      self.max_restarts,
+    )
+  }
+
+  // The settings for installing another tool the same way as the language
+  // server. Settings specific to the language server don't apply to it.
+  pub fn for_tool(&self, product: &'static Product) -> Config {
+    Config {
+      product,
+      version: None,
+      sha256: None,
+      releases_host: None,
+      download_url_template: None,
+      download_source: DownloadSource::Releases,
+      local_archive: None,
+      server_path: None,
+      server_log_file: None,
+      debug_port: None,
+      ..self.clone()
+    }
+  }
+
+  // The CLI the language server runs for the selected backend.
+  pub fn cli(&self) -> &'static str {
+    cli_name(self.product)
+  }
+
+  // A language server setting by its dotted path, like `terraform.path`.
+  pub fn server_setting(&self, path: &str) -> Option<&Value> {
+    path
+      .split('.')
+      .try_fold(self.lsp_options.as_ref()?, |v, key| v.get(key))
+  }
+
+  pub fn releases_host(&self) -> &str {
+    self
+      .releases_host
+      .as_deref()
+      .unwrap_or(self.product.releases_host)
+  }
+
+  pub fn download_url_template(&self) -> &str {
+    self
+      .download_url_template
+      .as_deref()
+      .unwrap_or(self.product.download_url_template)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn parse(volt: Value) -> Result<Config> {
+    Config::parse(Some(&json!({ "volt": volt })))
+  }
+
+  fn args(args: &[&str]) -> Result<Vec<String>> {
+    normalize_server_args(args.iter().map(|v| string!(*v)).collect())
+  }
+
+  #[test]
+  fn defaults_without_settings() {
+    let config = Config::parse(None).unwrap();
+    assert_eq!(config.product.name, TERRAFORM_LS.name);
+    assert!(config.channel == Channel::Stable);
+    assert_eq!(config.server_args, ["serve"]);
+    assert_eq!(config.keep_versions, 1);
+    assert_eq!(
+      config.download_timeout,
+      Some(Duration::from_secs(DOWNLOAD_TIMEOUT_SECS))
+    );
+    assert!(config.lsp_options.is_none());
+  }
+
+  #[test]
+  fn selects_the_backend_and_its_version() {
+    let config = parse(json!({
+      "backend": "opentofu-ls",
+      "terraformlsVersion": "0.32.7",
+      "tofulsVersion": " 0.1.0 ",
+    }))
+    .unwrap();
+    assert_eq!(config.product.name, TOFU_LS.name);
+    assert_eq!(config.version.as_deref(), Some("0.1.0"));
+    assert_eq!(config.cli(), "tofu");
+  }
+
+  #[test]
+  fn empty_settings_are_unset() {
+    let config = parse(json!({ "channel": "", "serverPath": "  ", "downloadTimeout": 0 })).unwrap();
+    assert!(config.channel == Channel::Stable);
+    assert!(config.server_path.is_none());
+    assert!(config.download_timeout.is_none());
+  }
+
+  #[test]
+  fn rejects_invalid_values() {
+    for volt in [
+      json!({ "channel": "nightly" }),
+      json!({ "backend": "terraform" }),
+      json!({ "terraformlsSha256": "abc" }),
+      json!({ "validation": { "mode": "always" } }),
+    ] {
+      let e = parse(volt).err().unwrap();
+      assert!(matches!(
+        e.downcast_ref(),
+        Some(PluginError::InvalidSetting { .. })
+      ));
+    }
+  }
+
+  #[test]
+  fn pins_terraform_ls_only() {
+    let sha256 = "AB".repeat(32);
+    let config = parse(json!({ "terraformlsSha256": sha256 })).unwrap();
+    assert_eq!(config.sha256, Some("ab".repeat(32)));
+
+    let config = parse(json!({ "backend": "tofu-ls", "terraformlsSha256": sha256 })).unwrap();
+    assert!(config.sha256.is_none());
+  }
+
+  #[test]
+  fn drops_unknown_settings_unless_strict() {
+    let config = parse(json!({ "chanel": "prerelease", "keepVersions": 3 })).unwrap();
+    assert_eq!(config.keep_versions, 3);
+
+    let config = parse(json!({ "checkov": { "enabled": true, "path": "/opt/checkov" } })).unwrap();
+    assert_eq!(config.checkov_path.as_deref(), Some("/opt/checkov"));
+
+    let e = parse(json!({ "chanel": "prerelease", "strictConfig": true }))
+      .err()
+      .unwrap();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::InvalidConfig { .. })
+    ));
+
+    let e = parse(json!({ "checkov": { "enabled": true }, "strictConfig": true }))
+      .err()
+      .unwrap();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::InvalidConfig { .. })
+    ));
+  }
+
+  #[test]
+  fn merges_volt_options_into_the_server_options() {
+    let config = Config::parse(Some(&json!({
+      "volt": {
+        "commandPrefix": "tf",
+        "prefillRequiredFields": true,
+        "validation": { "mode": "onSave" },
+      },
+      "terraform-ls": { "commandPrefix": "own" },
+    })))
+    .unwrap();
+    assert_eq!(
+      config.lsp_options,
+      Some(json!({
+        "commandPrefix": "own",
+        "experimentalFeatures": { "prefillRequiredFields": true, "validateOnSave": true },
+        "validation": { "enableEnhancedValidation": false },
+      }))
+    );
+  }
+
+  #[test]
+  fn prefers_top_level_settings_over_volt_options() {
+    let config = Config::parse(Some(&json!({
+      "volt": { "commandPrefix": "tf", "prefillRequiredFields": true },
+      "commandPrefix": "top",
+      "experimentalFeatures": { "prefillRequiredFields": false },
+    })))
+    .unwrap();
+    assert_eq!(
+      config.lsp_options,
+      Some(json!({
+        "commandPrefix": "top",
+        "experimentalFeatures": { "prefillRequiredFields": true },
+      }))
+    );
+
+    let config = Config::parse(Some(&json!({
+      "volt": { "commandPrefix": "tf" },
+      "commandPrefix": "",
+      "experimentalFeatures": { "prefillRequiredFields": true },
+    })))
+    .unwrap();
+    assert_eq!(
+      config.lsp_options,
+      Some(json!({
+        "commandPrefix": "tf",
+        "experimentalFeatures": { "prefillRequiredFields": true },
+      }))
+    );
+  }
+
+  #[test]
+  fn passes_the_cli_path_on_to_the_server() {
+    let config =
+      Config::parse(Some(&json!({ "terraform": { "path": "/opt/terraform" } }))).unwrap();
+    assert_eq!(
+      config.server_setting("terraform.path"),
+      Some(&json!("/opt/terraform"))
+    );
+
+    let config = Config::parse(Some(&json!({
+      "terraform": { "path": "/opt/terraform" },
+      "terraform-ls": { "terraform": { "path": "/usr/bin/terraform" } },
+    })))
+    .unwrap();
+    assert_eq!(
+      config.server_setting("terraform.path"),
+      Some(&json!("/usr/bin/terraform"))
+    );
+
+    let config = Config::parse(Some(&json!({ "terraform": { "path": "" } }))).unwrap();
+    assert!(config.lsp_options.is_none());
+  }
+
+  #[test]
+  fn normalizes_server_args() {
+    assert_eq!(
+      args(&[
+        "serve",
+        "--port 8080",
+        "-log-file",
+        "/tmp/terraform-ls.log",
+        "-tf-exec=/usr/bin/terraform",
+        " ",
+        "-req-concurrency",
+      ])
+      .unwrap(),
+      [
+        "-port=8080",
+        "-log-file=/tmp/terraform-ls.log",
+        "-tf-exec=/usr/bin/terraform",
+        "-req-concurrency",
+      ]
+    );
+    assert_eq!(args(&["-new-flag", "1"]).unwrap(), ["-new-flag=1"]);
+    assert!(args(&["port=8080"]).is_err());
+  }
+}
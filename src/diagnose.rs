@@ -0,0 +1,78 @@
+use crate::{cli, host::Host, install, server::host_path, State};
+
+fn first_line(output: Vec<u8>) -> String {
+  let output = String::from_utf8_lossy(&output);
+  string!(output.lines().next().unwrap_or_default().trim())
+}
+
+// A plain text report of the setup, meant to be pasted into bug reports.
+pub fn report(state: &State, host: &dyn Host) -> String {
+  let mut lines = vec![format!(
+    "lapce-terraform-ls {} diagnostics",
+    env!("CARGO_PKG_VERSION")
+  )];
+
+  let os = host.operating_system().unwrap_or_else(|e| e.to_string());
+  let arch = host.architecture().unwrap_or_else(|e| e.to_string());
+  let platform = match install::platform(host) {
+    | Ok(Ok((os, arch))) => format!("release builds for {os}_{arch}"),
+    | Ok(Err(reason)) => reason,
+    | Err(e) => e.to_string(),
+  };
+  lines.push(format!("Platform: {os}/{arch}, {platform}"));
+
+  match &state.config {
+    | Some(config) => lines.push(config.describe()),
+    | None => lines.push(string!("Config: not initialized yet")),
+  }
+
+  let server = match &state.server_uri {
+    | Some(uri) => {
+      let program = match uri.scheme() {
+        | "urn" => string!(uri.path()),
+        | _ => host_path(uri),
+      };
+      match host.execute(&program, vec![string!("version")]) {
+        | Some(output) => format!("{program}, {}", first_line(output)),
+        | None => format!("{program}, failed to run `{program} version`"),
+      }
+    }
+    | None => string!("not started"),
+  };
+  lines.push(format!("Language server: {server}"));
+
+  // The CLI the server runs, which isn't always the one on PATH.
+  let (cli, path) = match &state.config {
+    | Some(config) => (config.cli(), cli::cli_path(config)),
+    | None => ("terraform", string!("terraform")),
+  };
+  let cli_version = match host.execute(&path, vec![string!("version")]) {
+    | Some(output) => format!("{path}, {}", first_line(output)),
+    | None => format!("{path}, not found"),
+  };
+  lines.push(format!("{cli} CLI: {cli_version}"));
+
+  if let Some(config) = &state.config {
+    let releases_host = config.releases_host();
+    let reachable = match host.get(releases_host) {
+      | Ok(resp) => format!("reachable, HTTP {}", resp.status()),
+      | Err(e) => format!("unreachable, {e}"),
+    };
+    lines.push(format!("Releases host: {releases_host} {reachable}"));
+  }
+
+  let folders: Vec<String> = state
+    .params
+    .as_ref()
+    .and_then(|v| v.workspace_folders.as_ref())
+    .map(|v| v.iter().map(|v| host_path(&v.uri)).collect())
+    .unwrap_or_default();
+  let workspace = match (&state.root_uri, folders.is_empty()) {
+    | (None, true) => string!("none, single file mode"),
+    | (Some(root), true) => host_path(root),
+    | (_, false) => folders.join(", "),
+  };
+  lines.push(format!("Workspace: {workspace}"));
+
+  lines.join("\n")
+}
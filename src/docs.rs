@@ -0,0 +1,233 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use psp_types::{
+  lsp_types::{
+    request::{ShowDocument, ShowMessageRequest},
+    MessageActionItem, MessageType, ShowDocumentParams, ShowDocumentResult,
+    ShowMessageRequestParams, TextDocumentPositionParams, Url,
+  },
+  Request,
+};
+use serde_json::Value;
+
+use crate::{
+  config::Config,
+  host::Host,
+  lockfile,
+  rpc::PLUGIN_RPC,
+  server::{host_path, read_host_file},
+};
+
+// Every block type is a button, so only the first ones are offered.
+const PICKER_LIMIT: usize = 10;
+
+#[derive(PartialEq)]
+struct Block {
+  // The path segment of the docs, `resources` or `data-sources`.
+  kind: &'static str,
+  type_name: String,
+}
+
+// `resource "aws_s3_bucket" "logs" {` and `data "aws_ami" "ubuntu" {`, which
+// is enough without asking the server as block headers are on one line.
+fn parse_block(line: &str) -> Option<Block> {
+  let line = line.trim_start();
+  let (kind, rest) = match (line.strip_prefix("resource "), line.strip_prefix("data ")) {
+    | (Some(rest), _) => ("resources", rest),
+    | (_, Some(rest)) => ("data-sources", rest),
+    | _ => return None,
+  };
+  let type_name = rest.trim_start().strip_prefix('"')?.split('"').next()?;
+  if type_name.is_empty() {
+    return None;
+  }
+  Some(Block {
+    kind,
+    type_name: string!(type_name),
+  })
+}
+
+// The provider is the prefix of the type, its namespace is looked up in the
+// lock file and assumed to be `hashicorp` otherwise.
+fn docs_url(
+  config: &Config,
+  providers: &[lockfile::LockedProvider],
+  block: &Block,
+) -> Option<String> {
+  let (provider, name) = block.type_name.split_once('_')?;
+  let namespace = providers
+    .iter()
+    .find_map(|v| {
+      let mut parts = v.address.rsplit('/');
+      let (locked, namespace) = (parts.next()?, parts.next()?);
+      (locked == provider).then(|| string!(namespace))
+    })
+    .unwrap_or_else(|| string!("hashicorp"));
+  let registry = match config.product.name {
+    | "tofu-ls" => "https://search.opentofu.org/provider",
+    | _ => "https://registry.terraform.io/providers",
+  };
+  Some(format!(
+    "{registry}/{namespace}/{provider}/latest/docs/{}/{name}",
+    block.kind
+  ))
+}
+
+pub fn open_url(url: &str) -> Result<()> {
+  let uri = Url::parse(url).map_err(|e| anyhow!("Failed to open {url}: {e}"))?;
+  let result: Result<ShowDocumentResult, _> = PLUGIN_RPC.host_request(
+    ShowDocument::METHOD,
+    ShowDocumentParams {
+      uri,
+      external: Some(true),
+      take_focus: Some(true),
+      selection: None,
+    },
+  );
+  if !result.is_ok_and(|v| v.success) {
+    PLUGIN_RPC.window_show_message(MessageType::INFO, format!("Documentation: {url}"))?;
+  }
+  Ok(())
+}
+
+fn pick(blocks: Vec<Block>) -> Result<Option<Block>> {
+  let actions = blocks
+    .iter()
+    .take(PICKER_LIMIT)
+    .map(|v| MessageActionItem {
+      title: v.type_name.clone(),
+      properties: HashMap::new(),
+    })
+    .collect();
+  let picked: Option<MessageActionItem> = PLUGIN_RPC.host_request(
+    ShowMessageRequest::METHOD,
+    ShowMessageRequestParams {
+      typ: MessageType::INFO,
+      message: string!("Open the documentation of"),
+      actions: Some(actions),
+    },
+  )?;
+  Ok(picked.and_then(|picked| blocks.into_iter().find(|v| v.type_name == picked.title)))
+}
+
+// Opens the docs of the block under the cursor when the command comes with a
+// position, otherwise offers the blocks of the last opened or saved file.
+pub fn open(
+  host: &dyn Host,
+  config: &Config,
+  root_uri: Option<&Url>,
+  params: Value,
+  last_document: Option<&Url>,
+) -> Result<()> {
+  let position = serde_json::from_value::<TextDocumentPositionParams>(params).ok();
+  let Some(document) = position
+    .as_ref()
+    .map(|v| &v.text_document.uri)
+    .or(last_document)
+  else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      string!("Open a Terraform file to look up its documentation"),
+    )?;
+    return Ok(());
+  };
+  let path = host_path(document);
+  let contents = read_host_file(host, &path).ok_or_else(|| anyhow!("Failed to read {path}"))?;
+  let lines: Vec<&str> = contents.lines().collect();
+
+  let block = match &position {
+    // The nearest block header at or above the cursor.
+    | Some(position) => {
+      let line = usize::try_from(position.position.line)
+        .unwrap_or(usize::MAX)
+        .min(lines.len().saturating_sub(1));
+      lines
+        .iter()
+        .take(line + 1)
+        .rev()
+        .find_map(|v| parse_block(v))
+    }
+    | None => {
+      let mut blocks: Vec<Block> = vec![];
+      for block in lines.iter().filter_map(|v| parse_block(v)) {
+        if !blocks.contains(&block) {
+          blocks.push(block);
+        }
+      }
+      if blocks.is_empty() {
+        None
+      } else {
+        match pick(blocks)? {
+          | Some(v) => Some(v),
+          | None => return Ok(()),
+        }
+      }
+    }
+  };
+
+  let Some(block) = block else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("There is no resource or data source to look up in {path}"),
+    )?;
+    return Ok(());
+  };
+  let providers = root_uri
+    .and_then(|v| lockfile::read(host, v))
+    .unwrap_or_default();
+  let url = docs_url(config, &providers, &block)
+    .ok_or_else(|| anyhow!("Failed to tell the provider of {}", block.type_name))?;
+  open_url(&url)
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+
+  fn block(kind: &'static str, type_name: &str) -> Block {
+    Block {
+      kind,
+      type_name: string!(type_name),
+    }
+  }
+
+  #[test]
+  fn parses_block_headers() {
+    assert!(
+      parse_block(r#"resource "aws_s3_bucket" "logs" {"#)
+        == Some(block("resources", "aws_s3_bucket"))
+    );
+    assert!(
+      parse_block(r#"  data "aws_ami" "ubuntu" {"#) == Some(block("data-sources", "aws_ami"))
+    );
+    assert!(parse_block(r#"module "vpc" {"#).is_none());
+    assert!(parse_block(r#"resource "" "x" {"#).is_none());
+  }
+
+  #[test]
+  fn links_to_the_registry_docs() {
+    let config = Config::parse(None).unwrap();
+    let providers = [lockfile::LockedProvider {
+      address: string!("registry.terraform.io/integrations/github"),
+      version: string!("6.0.0"),
+      constraints: None,
+    }];
+    assert_eq!(
+      docs_url(&config, &providers, &block("resources", "github_repository")).as_deref(),
+      Some("https://registry.terraform.io/providers/integrations/github/latest/docs/resources/repository")
+    );
+    assert_eq!(
+      docs_url(&config, &providers, &block("data-sources", "aws_ami")).as_deref(),
+      Some("https://registry.terraform.io/providers/hashicorp/aws/latest/docs/data-sources/ami")
+    );
+
+    let config = Config::parse(Some(&json!({ "volt": { "backend": "tofu-ls" } }))).unwrap();
+    assert_eq!(
+      docs_url(&config, &[], &block("resources", "aws_instance")).as_deref(),
+      Some("https://search.opentofu.org/provider/hashicorp/aws/latest/docs/resources/instance")
+    );
+  }
+}
@@ -0,0 +1,225 @@
+use std::{fmt, time::Duration};
+
+#[derive(Debug)]
+pub enum PluginError {
+  ReleaseLookupFailed {
+    product: &'static str,
+    url: String,
+    reason: String,
+  },
+  VersionNotFound {
+    product: &'static str,
+    version: String,
+    url: String,
+  },
+  DownloadFailed {
+    product: &'static str,
+    version: String,
+    url: String,
+    reason: String,
+  },
+  DownloadTimedOut {
+    product: &'static str,
+    version: String,
+    timeout: Duration,
+  },
+  DownloadDeclined {
+    product: &'static str,
+    version: String,
+  },
+  NotAnArchive {
+    url: String,
+  },
+  LocalArchive {
+    path: String,
+  },
+  MissingBinary {
+    product: &'static str,
+    version: String,
+    source: String,
+  },
+  ChecksumMismatch {
+    product: &'static str,
+    source: String,
+    // Where the expected checksum comes from.
+    expected_by: String,
+    expected: String,
+    actual: String,
+  },
+  NotExecutable {
+    path: String,
+  },
+  UnsupportedPlatform {
+    product: &'static str,
+    reason: String,
+  },
+  BadServerPath {
+    path: String,
+    reason: String,
+  },
+  InvalidSetting {
+    name: &'static str,
+    value: String,
+  },
+  InvalidConfig {
+    reason: String,
+  },
+}
+
+impl PluginError {
+  // Short description shown to the user, details only go to the log.
+  pub fn summary(&self) -> String {
+    match self {
+      | PluginError::ReleaseLookupFailed { product, .. } => {
+        format!("Failed to look up {product} releases.")
+      }
+      | PluginError::VersionNotFound {
+        product, version, ..
+      } => format!("{product} version {version} does not exist for your platform."),
+      | PluginError::DownloadFailed {
+        product, version, ..
+      } => format!("Failed to download {product} {version}."),
+      | PluginError::DownloadTimedOut {
+        product, version, ..
+      } => format!("Downloading {product} {version} timed out."),
+      | PluginError::DownloadDeclined {
+        product, version, ..
+      } => format!("Downloading {product} {version} was declined."),
+      | PluginError::NotAnArchive { .. } => string!("The download is not an archive."),
+      | PluginError::LocalArchive { path } => format!("Failed to copy local archive {path}."),
+      | PluginError::MissingBinary {
+        product, version, ..
+      } => format!("{product} {version} is missing from the archive."),
+      | PluginError::ChecksumMismatch { product, .. } => {
+        format!("The {product} download doesn't match its checksum.")
+      }
+      | PluginError::NotExecutable { path } => format!("{path} can't be executed."),
+      | PluginError::UnsupportedPlatform { product, .. } => {
+        format!("There is no {product} build for your platform.")
+      }
+      | PluginError::BadServerPath { path, .. } => format!("Invalid `volt.serverPath` {path}."),
+      | PluginError::InvalidSetting { name, value } => {
+        format!("Invalid value `{value}` for `{name}`.")
+      }
+      | PluginError::InvalidConfig { .. } => string!("Invalid plugin settings."),
+    }
+  }
+
+  pub fn hint(&self) -> String {
+    match self {
+      | PluginError::ReleaseLookupFailed { .. } | PluginError::DownloadFailed { .. } => {
+        string!("Check your network connection or set `volt.releasesHost` to use a mirror.")
+      }
+      | PluginError::VersionNotFound { .. } => {
+        string!("Check `volt.terraformlsVersion` for typos or pick another version.")
+      }
+      | PluginError::DownloadTimedOut { .. } => string!(
+        "Use `volt.localArchive` to install it offline, `volt.releasesHost` to download it from \
+         a mirror or increase `volt.downloadTimeout`."
+      ),
+      | PluginError::DownloadDeclined { product, .. } => format!(
+        "Set `volt.allowDownloads` to let the plugin download it, or install it yourself and set \
+         `{}`.",
+        match *product {
+          | "trivy" => "volt.trivyPath",
+          | _ => "volt.serverPath",
+        }
+      ),
+      | PluginError::NotAnArchive { .. } => {
+        string!("Check that no proxy or captive portal is intercepting the download.")
+      }
+      | PluginError::LocalArchive { .. } => {
+        string!("Make sure `volt.localArchive` points to an existing file.")
+      }
+      | PluginError::MissingBinary { .. } => {
+        string!("Make sure the archive is a language server release.")
+      }
+      | PluginError::ChecksumMismatch { .. } => string!(
+        "The download may be corrupted or tampered with, reinstall to try again. With \
+         `volt.terraformlsSha256` set, check that it belongs to the configured version and \
+         platform."
+      ),
+      | PluginError::NotExecutable { path } => format!(
+        "Run `chmod +x {path}` or make sure the plugin directory isn't mounted with `noexec`."
+      ),
+      | PluginError::UnsupportedPlatform { .. } => {
+        string!("Install it manually and set `volt.serverPath`.")
+      }
+      | PluginError::BadServerPath { .. } => {
+        string!("Fix or clear `volt.serverPath` to use the bundled language server.")
+      }
+      | PluginError::InvalidSetting { .. } => string!("Check the plugin settings."),
+      | PluginError::InvalidConfig { reason } => format!("Check the plugin settings: {reason}."),
+    }
+  }
+}
+
+impl fmt::Display for PluginError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      | PluginError::ReleaseLookupFailed {
+        product,
+        url,
+        reason,
+      } => write!(f, "Failed to look up {product} releases at {url}: {reason}"),
+      | PluginError::VersionNotFound {
+        product,
+        version,
+        url,
+      } => write!(f, "{product} version {version} does not exist at {url}"),
+      | PluginError::DownloadFailed {
+        product,
+        version,
+        url,
+        reason,
+      } => write!(
+        f,
+        "Failed to download {product} {version} from {url}: {reason}"
+      ),
+      | PluginError::DownloadTimedOut {
+        product,
+        version,
+        timeout,
+      } => write!(
+        f,
+        "Downloading {product} {version} timed out after {}s",
+        timeout.as_secs()
+      ),
+      | PluginError::DownloadDeclined { product, version } => {
+        write!(f, "Downloading {product} {version} was declined")
+      }
+      | PluginError::NotAnArchive { url } => {
+        write!(f, "The response from {url} is not an archive")
+      }
+      | PluginError::LocalArchive { path } => write!(f, "Failed to copy local archive {path}"),
+      | PluginError::MissingBinary {
+        product,
+        version,
+        source,
+      } => write!(f, "{product} {version} was not found in {source}"),
+      | PluginError::ChecksumMismatch {
+        product,
+        source,
+        expected_by,
+        expected,
+        actual,
+      } => write!(
+        f,
+        "{product} from {source} has the SHA256 {actual}, but {expected_by} says {expected}"
+      ),
+      | PluginError::NotExecutable { path } => write!(f, "{path} can't be executed"),
+      | PluginError::UnsupportedPlatform { product, reason } => {
+        write!(f, "{reason} and no {product} was found on PATH")
+      }
+      | PluginError::BadServerPath { path, reason } => {
+        write!(f, "`volt.serverPath` {path} is invalid: {reason}")
+      }
+      | PluginError::InvalidSetting { name, value } => {
+        write!(f, "Invalid value `{value}` for `{name}`")
+      }
+      | PluginError::InvalidConfig { reason } => write!(f, "Invalid plugin settings: {reason}"),
+    }
+  }
+}
+
+impl std::error::Error for PluginError {}
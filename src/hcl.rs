@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{Diagnostic, DiagnosticSeverity, Position, Range, Url};
+
+use crate::{
+  config::Config,
+  host::Host,
+  ignore::glob_match,
+  scanner::{Findings, Scanned, Scanner},
+  server::{host_path, read_host_file},
+};
+
+// HCL files that are Terraform files and go to the language server anyway.
+const TERRAFORM_HCL: &[&str] = &[".terraform.lock.hcl", ".tftest.hcl"];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Open {
+  Brace,
+  Bracket,
+  Paren,
+  String,
+  // `${` or `%{` inside of a string.
+  Template,
+}
+
+impl Open {
+  fn describe(self) -> &'static str {
+    match self {
+      | Open::Brace => "`{`",
+      | Open::Bracket => "`[`",
+      | Open::Paren => "`(`",
+      | Open::String => "string",
+      | Open::Template => "template",
+    }
+  }
+}
+
+struct SyntaxError {
+  line: usize,
+  column: usize,
+  message: String,
+}
+
+fn error(line: usize, column: usize, message: String) -> Option<SyntaxError> {
+  Some(SyntaxError {
+    line,
+    column,
+    message,
+  })
+}
+
+// The first syntax error of an HCL file, as far as brackets, strings,
+// templates, heredocs and comments go. Block and attribute names aren't
+// checked, they differ between every tool using HCL.
+fn check(contents: &str) -> Option<SyntaxError> {
+  let mut open: Vec<(Open, usize, usize)> = vec![];
+  let mut block_comment: Option<(usize, usize)> = None;
+  let mut heredoc: Option<(String, usize)> = None;
+
+  for (line, text) in contents.lines().enumerate() {
+    if let Some((marker, _)) = &heredoc {
+      if text.trim() == marker {
+        heredoc = None;
+      }
+      continue;
+    }
+
+    let chars: Vec<char> = text.chars().collect();
+    let mut i = 0;
+    while i < chars.len() {
+      let (c, next) = (chars[i], chars.get(i + 1).copied());
+      if block_comment.is_some() {
+        if c == '*' && next == Some('/') {
+          block_comment = None;
+          i += 1;
+        }
+        i += 1;
+        continue;
+      }
+
+      let top = open.last().map(|v| v.0);
+      if top == Some(Open::String) {
+        match (c, next) {
+          | ('\\', _) => i += 1,
+          | ('"', _) => {
+            open.pop();
+          }
+          | ('$' | '%', Some('{')) => {
+            open.push((Open::Template, line, i));
+            i += 1;
+          }
+          | _ => {}
+        }
+        i += 1;
+        continue;
+      }
+
+      match (c, next) {
+        | ('#', _) | ('/', Some('/')) => break,
+        | ('/', Some('*')) => {
+          block_comment = Some((line, i));
+          i += 1;
+        }
+        | ('"', _) => open.push((Open::String, line, i)),
+        | ('<', Some('<')) => {
+          let marker: String = chars[i + 2..].iter().collect();
+          let marker = marker.trim_start_matches('-').trim();
+          if !marker.is_empty() && marker.chars().all(|v| v.is_alphanumeric() || v == '_') {
+            heredoc = Some((string!(marker), line));
+            break;
+          }
+          i += 1;
+        }
+        | ('{', _) => open.push((Open::Brace, line, i)),
+        | ('[', _) => open.push((Open::Bracket, line, i)),
+        | ('(', _) => open.push((Open::Paren, line, i)),
+        | ('}' | ']' | ')', _) => {
+          let expected = match c {
+            | '}' => [Open::Brace, Open::Template],
+            | ']' => [Open::Bracket, Open::Bracket],
+            | _ => [Open::Paren, Open::Paren],
+          };
+          match open.last() {
+            | Some((v, _, _)) if expected.contains(v) => {
+              open.pop();
+            }
+            | Some((v, l, col)) => {
+              return error(
+                line,
+                i,
+                format!(
+                  "Unexpected `{c}`, the {} at {}:{} isn't closed yet",
+                  v.describe(),
+                  l + 1,
+                  col + 1
+                ),
+              );
+            }
+            | None => return error(line, i, format!("Unexpected `{c}`, nothing is open")),
+          }
+        }
+        | _ => {}
+      }
+      i += 1;
+    }
+
+    // Only heredocs span lines, quoted strings end on the line they start.
+    if let Some((Open::String, l, col)) = open.last().copied() {
+      return error(l, col, string!("Unterminated string"));
+    }
+  }
+
+  if let Some((line, column)) = block_comment {
+    return error(line, column, string!("Unterminated comment"));
+  }
+  if let Some((marker, line)) = heredoc {
+    return error(
+      line,
+      0,
+      format!("Unterminated heredoc, `{marker}` is missing"),
+    );
+  }
+  open.last().and_then(|(v, line, column)| {
+    error(
+      *line,
+      *column,
+      format!("The {} is never closed", v.describe()),
+    )
+  })
+}
+
+// Syntax checks for HCL files that aren't Terraform files, like Nomad jobs or
+// Vault policies, matching `volt.hcl.patterns`.
+pub struct HclSyntax {
+  patterns: Vec<String>,
+}
+
+impl HclSyntax {
+  pub fn new(config: &Config) -> HclSyntax {
+    HclSyntax {
+      patterns: config.hcl_patterns.clone(),
+    }
+  }
+}
+
+impl Scanner for HclSyntax {
+  fn name(&self) -> &'static str {
+    "hcl"
+  }
+
+  fn applies_to(&self, path: &str) -> bool {
+    !TERRAFORM_HCL.iter().any(|v| path.ends_with(v))
+      && self.patterns.iter().any(|v| glob_match(v, path))
+  }
+
+  fn scan(&mut self, host: &dyn Host, _root_uri: &Url, saved: &Url) -> Result<(Scanned, Findings)> {
+    let path = host_path(saved);
+    let contents = read_host_file(host, &path).ok_or_else(|| anyhow!("Failed to read {path}"))?;
+    let diagnostics = check(&contents)
+      .map(|v| {
+        let position = Position {
+          line: v.line as u32,
+          character: v.column as u32,
+        };
+        Diagnostic {
+          range: Range {
+            start: position,
+            end: position,
+          },
+          severity: Some(DiagnosticSeverity::ERROR),
+          source: Some(string!("hcl")),
+          message: v.message,
+          ..Default::default()
+        }
+      })
+      .into_iter()
+      .collect();
+    Ok((
+      Scanned::File(saved.clone()),
+      Findings::from([(saved.clone(), diagnostics)]),
+    ))
+  }
+}
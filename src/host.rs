@@ -0,0 +1,292 @@
+use std::{
+  fs::{self, File, OpenOptions},
+  io::{self, Read, Seek, Write},
+  path::{Path, PathBuf},
+  sync::Mutex,
+  time::{SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+#[cfg(target_os = "wasi")]
+use lapce_plugin::{Http as LapceHttp, Response, VoltEnvironment, PLUGIN_RPC};
+
+use crate::server::{host_path, volt_file_uri};
+
+// Lapce's HTTP client only trusts the system certificates, so with a CA
+// bundle configured requests go through curl on the host instead, which also
+// ships with Windows 10 and later.
+static CA_BUNDLE: Mutex<Option<String>> = Mutex::new(None);
+
+pub fn set_ca_bundle(path: Option<String>) {
+  if let Ok(mut v) = CA_BUNDLE.lock() {
+    *v = path;
+  }
+}
+
+// Everything the plugin needs from Lapce and the sandbox goes through these
+// traits, so the install logic can run against mocks outside of Lapce.
+
+pub trait Http {
+  fn get(&self, url: &str) -> Result<Box<dyn HttpResponse>>;
+  // Size of what `url` serves as told by a HEAD request, without fetching it.
+  fn content_length(&self, url: &str) -> Option<u64>;
+}
+
+pub trait HttpResponse {
+  fn status(&self) -> u16;
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+  fn read_all(&mut self) -> Result<Vec<u8>>;
+}
+
+pub trait Environment {
+  fn uri(&self) -> Result<String>;
+  fn architecture(&self) -> Result<String>;
+  fn operating_system(&self) -> Result<String>;
+}
+
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek> ReadSeek for T {}
+
+// Paths are relative to the volt directory, the only one the sandbox sees.
+pub trait FileSystem {
+  fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+  fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()>;
+  fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>>;
+  fn create(&self, path: &Path) -> io::Result<Box<dyn Write>>;
+  // Fails with `AlreadyExists` if there is a file at `path` already.
+  fn create_new(&self, path: &Path) -> io::Result<()>;
+  fn modified(&self, path: &Path) -> io::Result<SystemTime>;
+  fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>>;
+  fn create_dir_all(&self, path: &Path) -> io::Result<()>;
+  fn remove_file(&self, path: &Path) -> io::Result<()>;
+  fn remove_dir_all(&self, path: &Path) -> io::Result<()>;
+  fn rename(&self, from: &Path, to: &Path) -> io::Result<()>;
+  fn exists(&self, path: &Path) -> bool;
+  fn is_dir(&self, path: &Path) -> bool;
+  fn is_file(&self, path: &Path) -> bool;
+}
+
+// Runs programs on the host, outside of the sandbox.
+pub trait Process {
+  // Returns the standard output, or `None` if the program couldn't be started
+  // or exited unsuccessfully.
+  fn execute(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>>;
+
+  // Returns the standard output whatever the exit status, for programs that
+  // exit unsuccessfully to report findings.
+  fn output(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>>;
+}
+
+pub trait Host: Http + Environment + FileSystem + Process {}
+
+impl<T: Http + Environment + FileSystem + Process> Host for T {}
+
+#[cfg(target_os = "wasi")]
+pub struct Lapce;
+
+#[cfg(target_os = "wasi")]
+impl Http for Lapce {
+  fn get(&self, url: &str) -> Result<Box<dyn HttpResponse>> {
+    let ca_bundle = CA_BUNDLE.lock().ok().and_then(|v| v.clone());
+    match ca_bundle {
+      | Some(ca_bundle) => Ok(Box::new(curl_get(self, url, &ca_bundle)?)),
+      | None => Ok(Box::new(LapceHttp::get(url)?)),
+    }
+  }
+
+  // Lapce's HTTP client only does GET requests, so this goes through curl,
+  // which prints the headers of every redirect followed.
+  fn content_length(&self, url: &str) -> Option<u64> {
+    let mut args = vec![
+      string!("--silent"),
+      string!("--head"),
+      string!("--location"),
+    ];
+    if let Some(ca_bundle) = CA_BUNDLE.lock().ok().and_then(|v| v.clone()) {
+      args.extend([string!("--cacert"), ca_bundle]);
+    }
+    args.push(string!(url));
+    let output = self.execute("curl", args)?;
+    String::from_utf8_lossy(&output)
+      .lines()
+      .filter_map(|v| v.split_once(':'))
+      .rfind(|(k, _)| k.trim().eq_ignore_ascii_case("content-length"))
+      .and_then(|(_, v)| v.trim().parse().ok())
+  }
+}
+
+// The body is written to a file in the volt directory, as the output of host
+// processes is only available once they have exited.
+#[cfg(target_os = "wasi")]
+struct CurlResponse {
+  status: u16,
+  body: File,
+  path: PathBuf,
+}
+
+#[cfg(target_os = "wasi")]
+impl Drop for CurlResponse {
+  fn drop(&mut self) {
+    let _ = fs::remove_file(&self.path);
+  }
+}
+
+#[cfg(target_os = "wasi")]
+fn curl_get(host: &Lapce, url: &str, ca_bundle: &str) -> Result<CurlResponse> {
+  let id = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|v| v.as_nanos())
+    .unwrap_or_default();
+  let path = PathBuf::from(format!("curl-{id}.body"));
+  let output = host
+    .execute(
+      "curl",
+      vec![
+        string!("--silent"),
+        string!("--show-error"),
+        string!("--location"),
+        string!("--cacert"),
+        string!(ca_bundle),
+        string!("--output"),
+        host_path(&volt_file_uri(host, &path)?),
+        string!("--write-out"),
+        string!("%{http_code}"),
+        string!(url),
+      ],
+    )
+    .ok_or_else(|| anyhow!("Failed to fetch {url} with curl using the CA bundle {ca_bundle}"))?;
+  let status = String::from_utf8_lossy(&output)
+    .trim()
+    .parse()
+    .map_err(|_| anyhow!("Failed to fetch {url} with curl, no HTTP status"))?;
+
+  Ok(CurlResponse {
+    status,
+    body: File::open(&path)?,
+    path,
+  })
+}
+
+#[cfg(target_os = "wasi")]
+impl HttpResponse for CurlResponse {
+  fn status(&self) -> u16 {
+    self.status
+  }
+
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    Ok(self.body.read(buf)?)
+  }
+
+  fn read_all(&mut self) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    self.body.read_to_end(&mut body)?;
+    Ok(body)
+  }
+}
+
+#[cfg(target_os = "wasi")]
+impl HttpResponse for Response {
+  fn status(&self) -> u16 {
+    self.status_code.as_u16()
+  }
+
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    self.body_read(buf)
+  }
+
+  fn read_all(&mut self) -> Result<Vec<u8>> {
+    self.body_read_all()
+  }
+}
+
+#[cfg(target_os = "wasi")]
+impl Environment for Lapce {
+  fn uri(&self) -> Result<String> {
+    Ok(VoltEnvironment::uri()?)
+  }
+
+  fn architecture(&self) -> Result<String> {
+    Ok(VoltEnvironment::architecture()?)
+  }
+
+  fn operating_system(&self) -> Result<String> {
+    Ok(VoltEnvironment::operating_system()?)
+  }
+}
+
+#[cfg(target_os = "wasi")]
+impl FileSystem for Lapce {
+  fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(path)
+  }
+
+  fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+    fs::write(path, contents)
+  }
+
+  fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    Ok(Box::new(File::open(path)?))
+  }
+
+  fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+    Ok(Box::new(File::create(path)?))
+  }
+
+  fn create_new(&self, path: &Path) -> io::Result<()> {
+    OpenOptions::new().write(true).create_new(true).open(path)?;
+    Ok(())
+  }
+
+  fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(path)?.modified()
+  }
+
+  fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::read_dir(path)?.map(|v| Ok(v?.path())).collect()
+  }
+
+  fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+    fs::create_dir_all(path)
+  }
+
+  fn remove_file(&self, path: &Path) -> io::Result<()> {
+    fs::remove_file(path)
+  }
+
+  fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+    fs::remove_dir_all(path)
+  }
+
+  fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(from, to)
+  }
+
+  fn exists(&self, path: &Path) -> bool {
+    path.exists()
+  }
+
+  fn is_dir(&self, path: &Path) -> bool {
+    path.is_dir()
+  }
+
+  fn is_file(&self, path: &Path) -> bool {
+    path.is_file()
+  }
+}
+
+#[cfg(target_os = "wasi")]
+impl Process for Lapce {
+  fn execute(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>> {
+    let output = PLUGIN_RPC.execute_process(program.to_owned(), args).ok()?;
+    if !output.success {
+      return None;
+    }
+    Some(output.stdout.unwrap_or_default())
+  }
+
+  fn output(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>> {
+    let output = PLUGIN_RPC.execute_process(program.to_owned(), args).ok()?;
+    Some(output.stdout.unwrap_or_default())
+  }
+}
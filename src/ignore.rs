@@ -0,0 +1,58 @@
+use psp_types::lsp_types::{Diagnostic, NumberOrString};
+
+// `*` matches any run of characters, everything else itself.
+pub fn glob_match(pattern: &str, text: &str) -> bool {
+  let (pattern, text) = (pattern.as_bytes(), text.as_bytes());
+  let (mut p, mut t) = (0, 0);
+  let mut backtrack = None;
+
+  while t < text.len() {
+    match pattern.get(p) {
+      | Some(b'*') => {
+        backtrack = Some((p, t));
+        p += 1;
+      }
+      | Some(&c) if c == text[t] => {
+        p += 1;
+        t += 1;
+      }
+      | _ => match backtrack {
+        | Some((bp, bt)) => {
+          backtrack = Some((bp, bt + 1));
+          p = bp + 1;
+          t = bt + 1;
+        }
+        | None => return false,
+      },
+    }
+  }
+  pattern[p..].iter().all(|v| *v == b'*')
+}
+
+// Glob patterns from `volt.diagnostics.ignore`, matched against the rule ID
+// and the message of diagnostics the plugin reports itself.
+#[derive(Clone, Default)]
+pub struct IgnoreList {
+  patterns: Vec<String>,
+}
+
+impl IgnoreList {
+  pub fn new(patterns: Vec<String>) -> IgnoreList {
+    IgnoreList { patterns }
+  }
+
+  pub fn is_ignored(&self, code: Option<&str>, message: &str) -> bool {
+    self.patterns.iter().any(|pattern| {
+      code.is_some_and(|code| glob_match(pattern, code)) || glob_match(pattern, message)
+    })
+  }
+
+  pub fn is_ignored_diagnostic(&self, diagnostic: &Diagnostic) -> bool {
+    let code = match &diagnostic.code {
+      | Some(NumberOrString::String(v)) => Some(v.clone()),
+      | Some(NumberOrString::Number(v)) => Some(v.to_string()),
+      | None => None,
+    };
+    self.is_ignored(code.as_deref(), &diagnostic.message)
+  }
+}
@@ -0,0 +1,74 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::Url;
+
+use crate::{
+  config::Config,
+  host::Host,
+  log,
+  server::{self, host_path},
+  tools::{Output, Tool},
+};
+
+// Release archives name the binary after the platform, so it isn't installed
+// by the plugin.
+const INFRACOST: &str = "infracost";
+
+// Estimates the monthly costs of the workspace with `infracost breakdown`.
+pub struct Infracost {
+  path: Option<String>,
+}
+
+impl Infracost {
+  pub fn new(config: &Config) -> Infracost {
+    Infracost {
+      path: config.infracost_path.clone(),
+    }
+  }
+}
+
+impl Tool for Infracost {
+  fn name(&self) -> &'static str {
+    INFRACOST
+  }
+
+  fn executable(&mut self, host: &dyn Host, root_uri: &Url) -> Result<String> {
+    match &self.path {
+      // There is no `version` subcommand to check it with.
+      | Some(path) => server::resolve_server_path(host, path, Some(root_uri)),
+      | None => Ok(string!(INFRACOST)),
+    }
+  }
+
+  fn run(&mut self, host: &dyn Host, executable: &str, root_uri: &Url) -> Result<Output> {
+    let root = host_path(root_uri);
+    let output = log::timed("infracost breakdown", || {
+      host.execute(
+        executable,
+        vec![
+          string!("breakdown"),
+          string!("--path"),
+          root.clone(),
+          string!("--format"),
+          string!("table"),
+          string!("--no-color"),
+        ],
+      )
+    })
+    .ok_or_else(|| {
+      anyhow!(
+        "Failed to estimate the costs of {root} with {executable}, make sure it is installed and \
+         authenticated with `infracost auth login` or `INFRACOST_API_KEY`"
+      )
+    })?;
+
+    let table = String::from_utf8_lossy(&output);
+    Ok(Output {
+      report: Some(format!(
+        "# Cost estimate\n\nMonthly costs of {root} as estimated by Infracost. Usage-based costs \
+         are only included with an `infracost-usage.yml`.\n\n```text\n{}\n```\n",
+        table.trim_end()
+      )),
+      ..Default::default()
+    })
+  }
+}
@@ -0,0 +1,1836 @@
+use std::{
+  cmp::{Ordering, Reverse},
+  collections::{BTreeMap, BTreeSet, HashMap},
+  fmt,
+  io::{self, BufReader, BufWriter, Read, Write},
+  path::{Path, PathBuf},
+  thread,
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::{anyhow, Result};
+use psp_types::{
+  lsp_types::{
+    request::ShowMessageRequest, MessageActionItem, MessageType, ShowMessageRequestParams,
+  },
+  Request,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use zip::ZipArchive;
+
+use crate::{
+  config::{Channel, Config, DownloadSource},
+  error::PluginError,
+  host::{Host, HttpResponse},
+  log,
+  rpc::PLUGIN_RPC,
+  server::{self, host_path, volt_file_uri},
+  state::PersistedState,
+};
+
+pub struct Product {
+  pub name: &'static str,
+  pub version: &'static str,
+  pub releases_host: &'static str,
+  pub download_url_template: &'static str,
+  // Products without an index on the releases host are listed through the
+  // GitHub API instead.
+  pub releases_index_template: Option<&'static str>,
+  pub github_releases_api: &'static str,
+  // Names of the platform in release archives, given the names used by
+  // HashiCorp.
+  pub platform_names: fn(&'static str, &'static str) -> (&'static str, &'static str),
+  // Replaces `{ext}` in the download URL template, given the OS.
+  pub archive_extension: fn(&'static str) -> &'static str,
+  // Name of the file listing the SHA256 of every archive of a release, next
+  // to the archives.
+  pub checksums_template: &'static str,
+}
+
+fn hashicorp_platform_names(os: &'static str, arch: &'static str) -> (&'static str, &'static str) {
+  (os, arch)
+}
+
+fn zip_extension(_os: &'static str) -> &'static str {
+  "zip"
+}
+
+pub const TERRAFORM_LS: Product = Product {
+  name: "terraform-ls",
+  version: "0.32.7",
+  releases_host: "https://releases.hashicorp.com",
+  download_url_template: "{host}/terraform-ls/{version}/terraform-ls_{version}_{os}_{arch}.{ext}",
+  releases_index_template: Some("{host}/terraform-ls/index.json"),
+  github_releases_api: "https://api.github.com/repos/hashicorp/terraform-ls/releases",
+  platform_names: hashicorp_platform_names,
+  archive_extension: zip_extension,
+  checksums_template: "terraform-ls_{version}_SHA256SUMS",
+};
+
+pub const TOFU_LS: Product = Product {
+  name: "tofu-ls",
+  version: "0.1.0",
+  releases_host: "https://github.com/opentofu/tofu-ls/releases/download",
+  download_url_template: "{host}/v{version}/tofu-ls_{version}_{os}_{arch}.{ext}",
+  releases_index_template: None,
+  github_releases_api: "https://api.github.com/repos/opentofu/tofu-ls/releases",
+  platform_names: hashicorp_platform_names,
+  archive_extension: zip_extension,
+  checksums_template: "tofu-ls_{version}_SHA256SUMS",
+};
+
+const LOCAL_VERSION: &str = "local";
+const INSTALLED_VERSIONS_FILE: &str = "installed.json";
+// Versions started this recently may still be running in another window, and
+// pinned ones started this recently are likely to be started again.
+const IN_USE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+const PINNED: Duration = Duration::from_secs(90 * 24 * 60 * 60);
+const INSTALL_MANIFEST_FILE: &str = "manifest.json";
+
+const DOWNLOAD_CHUNK_SIZE: usize = 64 * 1024;
+pub const DOWNLOAD_TIMEOUT_SECS: u64 = 300;
+const DOWNLOAD_PROGRESS_INTERVAL: usize = 5 * 1024 * 1024;
+
+const INSTALL_LOCK_POLL: Duration = Duration::from_millis(500);
+const INSTALL_LOCK_STALE: Duration = Duration::from_secs(10 * 60);
+// Files on Windows stay locked for a moment after the process using them
+// exited, or while a virus scanner looks at them.
+const REPLACE_ATTEMPTS: u32 = 5;
+const REPLACE_BACKOFF: Duration = Duration::from_millis(200);
+// Actions of the prompt before the first download.
+const ALLOW_DOWNLOADS: &str = "Download";
+const DECLINE_DOWNLOADS: &str = "Don't download";
+// Far above any release, the binary is all that's extracted.
+const MAX_EXTRACTED_SIZE: u64 = 1024 * 1024 * 1024;
+const ZIP_MAGIC: [u8; 4] = *b"PK\x03\x04";
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+#[derive(Default, Serialize, Deserialize)]
+struct InstalledVersions {
+  versions: Vec<String>,
+  // When each version was last started, in seconds since the epoch. Plugin
+  // instances in other windows may still run them.
+  #[serde(default)]
+  started: BTreeMap<String, u64>,
+  // Versions last started because a project or setting pins them.
+  #[serde(default)]
+  pinned: BTreeSet<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct InstallManifest {
+  version: String,
+  sha256: String,
+  url: String,
+  timestamp: u64,
+  // The host platform it was installed on, empty in manifests written by
+  // older releases of the plugin.
+  #[serde(default)]
+  os: String,
+  #[serde(default)]
+  arch: String,
+}
+
+impl InstallManifest {
+  // The build itself may be for another architecture the host can run, see
+  // `arch_candidates`, so it's the host platform that is compared.
+  fn is_for(&self, os: &str, arch: &str) -> bool {
+    (self.os.is_empty() && self.arch.is_empty()) || (self.os == os && self.arch == arch)
+  }
+}
+
+#[derive(Deserialize)]
+struct ReleaseIndex {
+  versions: serde_json::Map<String, Value>,
+}
+
+#[derive(Deserialize)]
+struct GithubRelease {
+  tag_name: String,
+  #[serde(default)]
+  prerelease: bool,
+  #[serde(default)]
+  assets: Vec<GithubAsset>,
+}
+
+#[derive(Deserialize)]
+struct GithubAsset {
+  name: String,
+  browser_download_url: String,
+}
+
+enum Artifact {
+  Remote(String),
+  Local(String),
+}
+
+impl Artifact {
+  fn source(&self) -> &str {
+    match self {
+      | Artifact::Remote(v) | Artifact::Local(v) => v,
+    }
+  }
+}
+
+// Held while installing a version, so plugin instances in other windows that
+// share the volt directory don't extract over each other.
+struct InstallLock<'a> {
+  host: &'a dyn Host,
+  path: PathBuf,
+}
+
+impl<'a> InstallLock<'a> {
+  fn acquire(host: &'a dyn Host, path: &Path) -> Result<Option<InstallLock<'a>>> {
+    match host.create_new(path) {
+      | Ok(()) => Ok(Some(InstallLock {
+        host,
+        path: path.to_path_buf(),
+      })),
+      | Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+      | Err(e) => Err(e.into()),
+    }
+  }
+
+  fn is_stale(host: &dyn Host, path: &Path) -> bool {
+    host
+      .modified(path)
+      .ok()
+      .and_then(|v| v.elapsed().ok())
+      .is_some_and(|v| v > INSTALL_LOCK_STALE)
+  }
+}
+
+impl Drop for InstallLock<'_> {
+  fn drop(&mut self) {
+    let _ = self.host.remove_file(&self.path);
+  }
+}
+
+#[derive(PartialEq, Eq)]
+pub struct Version {
+  major: u64,
+  minor: u64,
+  patch: u64,
+  pre: Option<String>,
+}
+
+impl Version {
+  pub fn parse(v: &str) -> Option<Version> {
+    let v = v.strip_prefix('v').unwrap_or(v);
+    let (core, pre) = match v.split_once('-') {
+      | Some((core, pre)) => (core, Some(string!(pre))),
+      | None => (v, None),
+    };
+    let mut parts = core.split('.').map(|v| v.parse().ok());
+    let (Some(Some(major)), Some(Some(minor)), Some(Some(patch)), None) =
+      (parts.next(), parts.next(), parts.next(), parts.next())
+    else {
+      return None;
+    };
+    Some(Version {
+      major,
+      minor,
+      patch,
+      pre,
+    })
+  }
+
+  pub fn is_prerelease(&self) -> bool {
+    self.pre.is_some()
+  }
+}
+
+// Pre-release tags by their dot-separated identifiers, numeric ones compared
+// numerically as in semver. Like with `beta2` and `beta10`, a number at the
+// end of an identifier is compared numerically as well.
+fn pre_release_cmp(a: &str, b: &str) -> Ordering {
+  let split = |v: &str| {
+    let text = v.trim_end_matches(|c: char| c.is_ascii_digit());
+    (string!(text), v[text.len()..].parse::<u64>().ok())
+  };
+  // Tags only equal as numbers, like `beta01` and `beta1`, still differ.
+  a.split('.')
+    .map(split)
+    .cmp(b.split('.').map(split))
+    .then_with(|| a.cmp(b))
+}
+
+impl Ord for Version {
+  fn cmp(&self, other: &Self) -> Ordering {
+    (self.major, self.minor, self.patch)
+      .cmp(&(other.major, other.minor, other.patch))
+      .then_with(|| match (&self.pre, &other.pre) {
+        | (None, None) => Ordering::Equal,
+        | (None, Some(_)) => Ordering::Greater,
+        | (Some(_), None) => Ordering::Less,
+        | (Some(a), Some(b)) => pre_release_cmp(a, b),
+      })
+  }
+}
+
+impl PartialOrd for Version {
+  fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+    Some(self.cmp(other))
+  }
+}
+
+impl fmt::Display for Version {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+    if let Some(pre) = &self.pre {
+      write!(f, "-{pre}")?;
+    }
+    Ok(())
+  }
+}
+
+// Maps the host platform onto the names used by release archives. The inner
+// error describes a platform there are no release builds for.
+pub fn platform(host: &dyn Host) -> Result<Result<(&'static str, &'static str), String>> {
+  let arch = match host.architecture().as_deref() {
+    | Ok("x86") => Ok("386"),
+    | Ok("x86_64") => Ok("amd64"),
+    | Ok("aarch64") => Ok("arm64"),
+    | Ok("arm") => Ok("arm"),
+    | Ok(v) => Err(format!("Unsupported ARCH: {}", v)),
+    | Err(e) => return Err(anyhow!("Error ARCH: {}", e)),
+  };
+
+  let os = match host.operating_system().as_deref() {
+    | Ok("macos") => Ok("darwin"),
+    | Ok("linux") => Ok("linux"),
+    | Ok("windows") => Ok("windows"),
+    | Ok("openbsd") => Ok("openbsd"),
+    | Ok("freebsd") => Ok("freebsd"),
+    | Ok(v) => Err(format!("Unsupported OS: {}", v)),
+    | Err(e) => return Err(anyhow!("Error OS: {}", e)),
+  };
+
+  Ok(os.and_then(|os| arch.map(|arch| (os, arch))))
+}
+
+pub fn binary_name(host: &dyn Host, product: &Product) -> String {
+  match host.operating_system().as_deref() {
+    | Ok("windows") => format!("{}.exe", product.name),
+    | _ => string!(product.name),
+  }
+}
+
+// Builds to try in order, falling back to ones the platform can emulate when
+// a release has no native build.
+fn arch_candidates(os: &'static str, arch: &'static str) -> Vec<&'static str> {
+  match (os, arch) {
+    // Rosetta 2 on macOS, x64 emulation on Windows 11
+    | ("darwin" | "windows", "arm64") => vec!["arm64", "amd64"],
+    | (_, arch) => vec![arch],
+  }
+}
+
+fn download_url(
+  template: &str,
+  host: &str,
+  version: &str,
+  os: &str,
+  arch: &str,
+  ext: &str,
+) -> String {
+  template
+    .replace("{host}", host.trim_end_matches('/'))
+    .replace("{version}", version)
+    .replace("{os}", os)
+    .replace("{arch}", arch)
+    .replace("{ext}", ext)
+}
+
+// GitHub release assets carry the same names as the archives on the releases
+// host, so the asset is looked up by the local archive name.
+fn github_download_url(
+  host: &dyn Host,
+  product: &Product,
+  version: &str,
+  archive: &Path,
+) -> Result<String> {
+  let release_url = format!("{}/tags/v{version}", product.github_releases_api);
+  let mut resp = host.get(&release_url)?;
+  if !(200..300).contains(&resp.status()) {
+    return Err(
+      PluginError::ReleaseLookupFailed {
+        product: product.name,
+        url: release_url,
+        reason: format!("HTTP {}", resp.status()),
+      }
+      .into(),
+    );
+  }
+
+  let release: GithubRelease = serde_json::from_slice(&resp.read_all()?)?;
+  let asset_name = archive.to_string_lossy();
+  match release.assets.into_iter().find(|v| v.name == asset_name) {
+    | Some(asset) => Ok(asset.browser_download_url),
+    | None => Err(
+      PluginError::VersionNotFound {
+        product: product.name,
+        version: string!(version),
+        url: format!("{release_url} ({asset_name})"),
+      }
+      .into(),
+    ),
+  }
+}
+
+fn available_versions(
+  host: &dyn Host,
+  product: &Product,
+  releases_host: &str,
+) -> Result<Vec<Version>> {
+  let index_url = match product.releases_index_template {
+    | Some(template) => template.replace("{host}", releases_host.trim_end_matches('/')),
+    | None => format!("{}?per_page=100", product.github_releases_api),
+  };
+  let mut resp = host.get(&index_url)?;
+  if !(200..300).contains(&resp.status()) {
+    return Err(
+      PluginError::ReleaseLookupFailed {
+        product: product.name,
+        url: index_url,
+        reason: format!("HTTP {}", resp.status()),
+      }
+      .into(),
+    );
+  }
+  let body = resp.read_all()?;
+
+  let versions: Vec<Version> = match product.releases_index_template {
+    | Some(_) => serde_json::from_slice::<ReleaseIndex>(&body)?
+      .versions
+      .keys()
+      .filter_map(|v| Version::parse(v))
+      .collect(),
+    // GitHub flags pre-releases separately, which is trusted over the tag.
+    | None => serde_json::from_slice::<Vec<GithubRelease>>(&body)?
+      .into_iter()
+      .filter_map(|v| {
+        let mut version = Version::parse(&v.tag_name)?;
+        if v.prerelease && version.pre.is_none() {
+          version.pre = Some(string!("pre"));
+        }
+        Some(version)
+      })
+      .collect(),
+  };
+
+  Ok(versions)
+}
+
+// Versions of the configured channel, newest first.
+pub fn list_versions(host: &dyn Host, config: &Config) -> Result<Vec<String>> {
+  let mut versions: Vec<Version> =
+    available_versions(host, config.product, config.releases_host())?
+      .into_iter()
+      .filter(|v| config.channel == Channel::Prerelease || !v.is_prerelease())
+      .collect();
+  versions.sort_by(|a, b| b.cmp(a));
+  Ok(versions.iter().map(|v| v.to_string()).collect())
+}
+
+fn latest_version(
+  host: &dyn Host,
+  product: &Product,
+  releases_host: &str,
+  channel: &Channel,
+) -> Result<String> {
+  available_versions(host, product, releases_host)?
+    .into_iter()
+    .filter(|v| *channel == Channel::Prerelease || !v.is_prerelease())
+    .max()
+    .map(|v| v.to_string())
+    .ok_or_else(|| {
+      PluginError::ReleaseLookupFailed {
+        product: product.name,
+        url: string!(releases_host),
+        reason: string!("no releases found"),
+      }
+      .into()
+    })
+}
+
+// Picks the version to install, `pinned` takes precedence over the channel.
+pub fn resolve_version(
+  host: &dyn Host,
+  config: &Config,
+  pinned: Option<String>,
+  binary_name: &str,
+) -> Result<String> {
+  let product = config.product;
+  match (pinned, &config.channel) {
+    | (Some(v), _) if v != "latest" => Ok(v),
+    | (None, Channel::Stable) => Ok(string!(product.version)),
+    | (_, _) if config.local_archive.is_some() => Ok(string!(product.version)),
+    | (_, channel) => match latest_version(host, product, config.releases_host(), channel) {
+      | Ok(v) => Ok(v),
+      | Err(e) => {
+        // Installed for another platform is fine, ensure_installed reinstalls
+        // it then.
+        let Some(cached) = cached_version(host, product, binary_name, None) else {
+          return Err(e);
+        };
+        warn!(format!(
+          "Failed to look up the latest {} release, using the installed {cached}: {e}",
+          product.name
+        ));
+        Ok(cached)
+      }
+    },
+  }
+}
+
+fn read_manifest(host: &dyn Host, install_dir: &Path) -> Option<InstallManifest> {
+  host
+    .read(&install_dir.join(INSTALL_MANIFEST_FILE))
+    .ok()
+    .and_then(|v| serde_json::from_slice(&v).ok())
+}
+
+fn write_manifest(host: &dyn Host, install_dir: &Path, manifest: &InstallManifest) -> Result<()> {
+  host.write(
+    &install_dir.join(INSTALL_MANIFEST_FILE),
+    &serde_json::to_vec_pretty(manifest)?,
+  )?;
+  Ok(())
+}
+
+fn read_installed_versions(host: &dyn Host, product: &Product) -> InstalledVersions {
+  host
+    .read(&PathBuf::from(product.name).join(INSTALLED_VERSIONS_FILE))
+    .ok()
+    .and_then(|v| serde_json::from_slice(&v).ok())
+    .unwrap_or_default()
+}
+
+// The newest previously installed version that is still complete on disk.
+fn cached_version(
+  host: &dyn Host,
+  product: &Product,
+  binary_name: &str,
+  platform: Option<(&str, &str)>,
+) -> Option<String> {
+  read_installed_versions(host, product)
+    .versions
+    .into_iter()
+    .filter(|v| {
+      let install_dir = PathBuf::from(product.name).join(v);
+      host.exists(&install_dir.join(binary_name))
+        && read_manifest(host, &install_dir)
+          .is_some_and(|m| platform.is_none_or(|(os, arch)| m.is_for(os, arch)))
+    })
+    .max_by_key(|v| Version::parse(v))
+}
+
+fn write_installed_versions(
+  host: &dyn Host,
+  product: &Product,
+  installed: &InstalledVersions,
+) -> Result<()> {
+  host.write(
+    &PathBuf::from(product.name).join(INSTALLED_VERSIONS_FILE),
+    &serde_json::to_vec(installed)?,
+  )?;
+  Ok(())
+}
+
+// Writes the response body to `path` chunk by chunk, hashing it on the way so
+// the archive never has to be held in memory as a whole. Returns `None` when
+// `deadline` passes before the body has been read completely.
+fn stream_to_file(
+  host: &dyn Host,
+  resp: &mut dyn HttpResponse,
+  path: &Path,
+  deadline: Option<Instant>,
+) -> Result<Option<String>> {
+  let mut file = BufWriter::new(host.create(path)?);
+  let mut hasher = Sha256::new();
+  let mut buf = vec![0; DOWNLOAD_CHUNK_SIZE];
+  let mut downloaded = 0;
+  // Hashing is interleaved with reading, so its share is tracked separately.
+  let mut hashing = Duration::ZERO;
+  let started = Instant::now();
+
+  loop {
+    if deadline.is_some_and(|v| Instant::now() > v) {
+      return Ok(None);
+    }
+    let read = resp.read(&mut buf)?;
+    if read == 0 {
+      break;
+    }
+    file.write_all(&buf[..read])?;
+    let hash_started = Instant::now();
+    hasher.update(&buf[..read]);
+    hashing += hash_started.elapsed();
+
+    let reported = downloaded / DOWNLOAD_PROGRESS_INTERVAL;
+    downloaded += read;
+    if downloaded / DOWNLOAD_PROGRESS_INTERVAL > reported {
+      info!(format!("Downloaded {} MiB", downloaded / 1024 / 1024));
+    }
+  }
+  file.flush()?;
+  let sha256 = hex(&hasher.finalize());
+  log::duration("download", started.elapsed().saturating_sub(hashing));
+  log::duration("checksum", hashing);
+
+  Ok(Some(sha256))
+}
+
+fn sha256_file(host: &dyn Host, path: &Path) -> Result<String> {
+  let mut hasher = Sha256::new();
+  io::copy(&mut host.open(path)?, &mut hasher)?;
+  Ok(hex(&hasher.finalize()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+enum ArchiveKind {
+  Zip,
+  Tarball,
+}
+
+fn archive_kind(host: &dyn Host, path: &Path) -> Result<Option<ArchiveKind>> {
+  let mut magic = [0; 4];
+  match host.open(path)?.read_exact(&mut magic) {
+    | Ok(()) if magic == ZIP_MAGIC => Ok(Some(ArchiveKind::Zip)),
+    | Ok(()) if magic[..2] == GZIP_MAGIC => Ok(Some(ArchiveKind::Tarball)),
+    | Ok(()) => Ok(None),
+    | Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+    | Err(e) => Err(e.into()),
+  }
+}
+
+// Download artifacts left in the volt directory by `product`.
+fn is_artifact(product: &Product, path: &Path) -> bool {
+  let prefix = format!("{}_", product.name);
+  path
+    .file_name()
+    .and_then(|v| v.to_str())
+    .is_some_and(|v| v.starts_with(&prefix) && (v.ends_with(".zip") || v.ends_with(".tar.gz")))
+}
+
+// Retries `f` with backoff, for file operations that fail on Windows while
+// another process still has the files open.
+fn retry(what: &str, mut f: impl FnMut() -> io::Result<()>) -> io::Result<()> {
+  let mut backoff = REPLACE_BACKOFF;
+  let mut attempt = 1;
+  loop {
+    match f() {
+      | Err(e) if attempt < REPLACE_ATTEMPTS => {
+        debug!(format!(
+          "Failed to {what} ({e}), retrying in {}ms",
+          backoff.as_millis()
+        ));
+        thread::sleep(backoff);
+        backoff *= 2;
+        attempt += 1;
+      }
+      | result => return result,
+    }
+  }
+}
+
+// Running binaries can't be removed on Windows, which the server still is when
+// reinstalling the same version. Stops every server started from `dir`,
+// including those of other windows, as they would keep it locked.
+fn stop_servers_in(host: &dyn Host, dir: &Path) -> Result<()> {
+  if !server::is_windows(host) {
+    return Ok(());
+  }
+  let path = host_path(&volt_file_uri(host, dir)?);
+  let script = format!(
+    "Get-Process | Where-Object {{ $_.Path -and (Split-Path -Parent $_.Path) -eq '{}' }} | \
+     Stop-Process -Force",
+    path.trim_end_matches('\\').replace('\'', "''")
+  );
+  debug!(format!("Stopping servers started from {path}"));
+  host.execute(
+    "powershell",
+    vec![
+      string!("-NoProfile"),
+      string!("-NonInteractive"),
+      string!("-Command"),
+      script,
+    ],
+  );
+  Ok(())
+}
+
+// Install directories of `version`, newest first. Besides `<version>` itself
+// these are `<version>~<n>`, installed side by side when the previous one
+// couldn't be replaced.
+fn install_dirs(host: &dyn Host, product: &Product, version: &str) -> Vec<PathBuf> {
+  let prefix = format!("{version}~");
+  let mut side_by_side: Vec<(u32, PathBuf)> = host
+    .read_dir(Path::new(product.name))
+    .unwrap_or_default()
+    .into_iter()
+    .filter_map(|path| {
+      let n = path
+        .file_name()?
+        .to_str()?
+        .strip_prefix(&prefix)?
+        .parse()
+        .ok()?;
+      Some((n, path))
+    })
+    .collect();
+  side_by_side.sort_by_key(|v| Reverse(v.0));
+  let mut dirs: Vec<PathBuf> = side_by_side.into_iter().map(|(_, v)| v).collect();
+  dirs.push(PathBuf::from(product.name).join(version));
+  dirs
+}
+
+// Moves the extracted `temp_dir` to `install_dir`, replacing what's there.
+// When that remains locked, it goes next to it instead and the old one is left
+// for garbage collection. Returns where it ended up.
+fn move_into_place(host: &dyn Host, temp_dir: &Path, install_dir: &Path) -> Result<PathBuf> {
+  if host.exists(install_dir) {
+    stop_servers_in(host, install_dir)?;
+    let removed = retry(&format!("remove {}", install_dir.display()), || {
+      host.remove_dir_all(install_dir)
+    });
+    if let Err(e) = removed {
+      let name = install_dir
+        .file_name()
+        .and_then(|v| v.to_str())
+        .unwrap_or_default();
+      let side_by_side = (1..)
+        .map(|n| install_dir.with_file_name(format!("{name}~{n}")))
+        .find(|v| !host.exists(v))
+        .unwrap_or_default();
+      info!(format!(
+        "Failed to replace {} ({e}), installing into {} instead",
+        install_dir.display(),
+        side_by_side.display()
+      ));
+      host.rename(temp_dir, &side_by_side)?;
+      return Ok(side_by_side);
+    }
+  }
+  retry(&format!("rename {}", temp_dir.display()), || {
+    host.rename(temp_dir, install_dir)
+  })?;
+  Ok(install_dir.to_path_buf())
+}
+
+// Another instance is installing while it holds a lock in the product
+// directory, its temporary files are still in use then.
+fn is_installing(host: &dyn Host, product: &Product) -> bool {
+  host
+    .read_dir(Path::new(product.name))
+    .unwrap_or_default()
+    .iter()
+    .any(|v| v.extension().and_then(|v| v.to_str()) == Some("lock"))
+}
+
+// Records that the install directory `dir` was started, and whether its
+// version was pinned, which keeps it from being garbage collected.
+pub fn record_start(host: &dyn Host, product: &Product, dir: &str, pinned: bool) -> Result<()> {
+  let mut installed = read_installed_versions(host, product);
+  installed.started.insert(
+    string!(dir),
+    SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+  );
+  match pinned {
+    | true => installed.pinned.insert(string!(dir)),
+    | false => installed.pinned.remove(dir),
+  };
+  write_installed_versions(host, product, &installed)
+}
+
+// Removes installed versions besides `current` and the `keep - 1` installed
+// most recently before it, only called once `current` is known to work.
+// Versions that may still be running or are pinned are kept regardless.
+// Returns how many were removed.
+pub fn collect_garbage(
+  host: &dyn Host,
+  product: &Product,
+  current: &str,
+  keep: usize,
+) -> Result<usize> {
+  let previous = read_installed_versions(host, product);
+  let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+  let in_use: BTreeSet<String> = previous
+    .started
+    .iter()
+    .filter(|(version, &started)| {
+      let age = now.saturating_sub(started);
+      age < IN_USE.as_secs() || (previous.pinned.contains(*version) && age < PINNED.as_secs())
+    })
+    .map(|(version, _)| version.clone())
+    .collect();
+
+  let mut others = vec![];
+  for path in host.read_dir(Path::new(product.name))? {
+    let Some(version) = path.file_name().and_then(|v| v.to_str()).map(String::from) else {
+      continue;
+    };
+    if !host.is_dir(&path) || version == current || version.ends_with(".partial") {
+      continue;
+    }
+    // Versions without a manifest never finished installing.
+    let timestamp = read_manifest(host, &path).map(|v| v.timestamp);
+    others.push((timestamp, version, path));
+  }
+  others.sort_by_key(|v| Reverse(v.0));
+
+  let mut installed = InstalledVersions {
+    versions: vec![string!(current)],
+    ..previous
+  };
+  let mut removed = 0;
+  let mut kept = 1;
+  for (timestamp, version, path) in others {
+    if timestamp.is_some() && in_use.contains(&version) {
+      installed.versions.push(version);
+      continue;
+    }
+    if timestamp.is_some() && kept < keep {
+      kept += 1;
+      installed.versions.push(version);
+      continue;
+    }
+    match host.remove_dir_all(&path) {
+      | Ok(()) => removed += 1,
+      | Err(e) => {
+        error!(format!(
+          "Failed to remove old version {}: {e}",
+          path.display()
+        ));
+        installed.versions.push(version);
+      }
+    }
+  }
+
+  let versions: BTreeSet<&String> = installed.versions.iter().collect();
+  installed.started.retain(|v, _| versions.contains(v));
+  installed.pinned.retain(|v| versions.contains(v));
+  write_installed_versions(host, product, &installed)?;
+  Ok(removed)
+}
+
+// Makes sure `version` is installed, trying every build the platform can run.
+// Returns the path of the binary to start and whether it was installed just
+// now, falling back to a previously installed version when installing fails.
+pub fn ensure_installed(
+  host: &dyn Host,
+  config: &Config,
+  version: &str,
+  os: &'static str,
+  arch: &'static str,
+  binary_name: &str,
+) -> Result<(PathBuf, bool)> {
+  let product = config.product;
+
+  // A local artifact carries no version information, so it gets an install
+  // directory of its own that is replaced whenever the configured path changes.
+  let install_version = match config.local_archive {
+    | Some(_) => string!(LOCAL_VERSION),
+    | None => string!(version),
+  };
+  let install_dir = PathBuf::from(product.name).join(&install_version);
+
+  let mut installed = None;
+  let mut other_platform = None;
+  for dir in install_dirs(host, product, &install_version) {
+    let manifest = read_manifest(host, &dir).filter(|m| {
+      m.version == install_version
+        && config.local_archive.as_ref().is_none_or(|v| &m.url == v)
+        && config.sha256.as_ref().is_none_or(|v| &m.sha256 == v)
+    });
+    let Some(manifest) = manifest.filter(|_| host.exists(&dir.join(binary_name))) else {
+      continue;
+    };
+    // E.g. a plugin directory carried over from an Intel Mac to Apple
+    // Silicon.
+    if !manifest.is_for(os, arch) {
+      other_platform = Some(format!("{}/{}", manifest.os, manifest.arch));
+      continue;
+    }
+    installed = Some(dir);
+    break;
+  }
+  if let (None, Some(other)) = (&installed, other_platform) {
+    warn!(format!(
+      "{} {install_version} was installed for {other}, reinstalling it for {os}/{arch}",
+      product.name
+    ));
+  }
+  if let Some(dir) = installed {
+    return Ok((dir.join(binary_name), false));
+  }
+
+  let archs = match config.local_archive {
+    | Some(_) => vec![arch],
+    | None => arch_candidates(os, arch),
+  };
+
+  let host_platform = (os, arch);
+  let mut result = Ok(install_dir.clone());
+  for (i, arch) in archs.iter().enumerate() {
+    let (platform_os, platform_arch) = (product.platform_names)(os, arch);
+    let ext = (product.archive_extension)(os);
+    let archive = format!(
+      "{}_{version}_{platform_os}_{platform_arch}.{ext}",
+      product.name
+    );
+
+    debug!(format!("Trying {}", archive));
+
+    let archive = PathBuf::from(archive);
+
+    result = match (&config.local_archive, &config.download_source) {
+      | (Some(path), _) => Ok(Artifact::Local(path.clone())),
+      | (None, DownloadSource::Releases) => Ok(Artifact::Remote(download_url(
+        config.download_url_template(),
+        config.releases_host(),
+        version,
+        platform_os,
+        platform_arch,
+        ext,
+      ))),
+      | (None, DownloadSource::Github) => {
+        github_download_url(host, product, version, &archive).map(Artifact::Remote)
+      }
+    }
+    .and_then(|artifact| {
+      install(
+        host,
+        product,
+        &install_version,
+        &artifact,
+        &archive,
+        &install_dir,
+        binary_name,
+        config.download_timeout,
+        config.sha256.as_deref(),
+        config.allow_downloads,
+        host_platform,
+      )
+    });
+
+    match (&result, archs.get(i + 1)) {
+      | (Ok(_), _) | (Err(_), None) => break,
+      | (Err(e), Some(next)) => {
+        info!(format!(
+          "No {arch} build of {} {version} could be installed ({e}), trying {next}",
+          product.name
+        ));
+      }
+    }
+  }
+
+  // Keep the language server working with whatever was installed before
+  // when the network is unreachable.
+  match result {
+    | Ok(dir) => {
+      let current = dir.file_name().and_then(|v| v.to_str()).unwrap_or_default();
+      collect_garbage(host, product, current, config.keep_versions)?;
+      Ok((dir.join(binary_name), true))
+    }
+    | Err(e) => {
+      // A pinned checksum rules out every other build.
+      let cached = cached_version(host, product, binary_name, Some(host_platform)).filter(|v| {
+        config.sha256.as_ref().is_none_or(|sha256| {
+          read_manifest(host, &PathBuf::from(product.name).join(v))
+            .is_some_and(|m| &m.sha256 == sha256)
+        })
+      });
+      let Some(cached) = cached else {
+        return Err(e);
+      };
+      warn!(format!(
+        "Failed to install {} {install_version}, using the installed {cached}: {e}",
+        product.name
+      ));
+      Ok((
+        PathBuf::from(product.name).join(cached).join(binary_name),
+        false,
+      ))
+    }
+  }
+}
+
+// Fetches and extracts `version` into a temporary directory next to
+// `install_dir`, which is only moved into place once the archive has been
+// fully extracted and the binary and manifest are present. Returns the
+// directory it was installed into, see `move_into_place`.
+#[allow(clippy::too_many_arguments)]
+fn install(
+  host: &dyn Host,
+  product: &Product,
+  version: &str,
+  artifact: &Artifact,
+  archive: &Path,
+  install_dir: &Path,
+  binary_name: &str,
+  timeout: Option<Duration>,
+  pinned_sha256: Option<&str>,
+  allow_downloads: bool,
+  platform: (&str, &str),
+) -> Result<PathBuf> {
+  let temp_dir = install_dir.with_file_name(format!("{version}.partial"));
+  let lock_path = install_dir.with_file_name(format!("{version}.lock"));
+
+  if let Some(parent) = lock_path.parent() {
+    host.create_dir_all(parent)?;
+  }
+  let _lock = loop {
+    if let Some(lock) = InstallLock::acquire(host, &lock_path)? {
+      break lock;
+    }
+    if InstallLock::is_stale(host, &lock_path) {
+      host.remove_file(&lock_path)?;
+      continue;
+    }
+    thread::sleep(INSTALL_LOCK_POLL);
+  };
+
+  // Another instance may have finished installing while this one waited.
+  if host.exists(&install_dir.join(binary_name))
+    && read_manifest(host, install_dir).is_some_and(|m| {
+      m.version == version
+        && m.url == artifact.source()
+        && pinned_sha256.is_none_or(|v| m.sha256 == v)
+        && m.is_for(platform.0, platform.1)
+    })
+  {
+    return Ok(install_dir.to_path_buf());
+  }
+
+  if host.exists(archive) {
+    host.remove_file(archive)?;
+  }
+  // Older releases of this plugin put the binary directly into the volt
+  // directory, under the same name as the install directory.
+  if host.is_file(Path::new(product.name)) {
+    host.remove_file(Path::new(product.name))?;
+  }
+  if host.exists(&temp_dir) {
+    host.remove_dir_all(&temp_dir)?;
+  }
+  host.create_dir_all(&temp_dir)?;
+
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!(
+      "Installing {} {version}, this may take a moment",
+      product.name
+    ),
+  )?;
+  info!(format!("Fetching {}", artifact.source()));
+
+  let result = fetch_and_extract(
+    host,
+    product,
+    version,
+    artifact,
+    archive,
+    &temp_dir,
+    binary_name,
+    timeout,
+    pinned_sha256,
+    allow_downloads,
+    platform,
+  );
+
+  if let Err(e) = host.remove_file(archive) {
+    error!(format!(
+      "Failed to remove download artifact! L: {} C: {} e: {e}",
+      line!(),
+      column!()
+    ));
+  };
+
+  if let Err(e) = result {
+    if let Err(e) = host.remove_dir_all(&temp_dir) {
+      error!(format!("Failed to remove {}: {e}", temp_dir.display()));
+    }
+    return Err(e);
+  }
+
+  let install_dir = move_into_place(host, &temp_dir, install_dir)?;
+  debug!(format!("Installed into {}", install_dir.display()));
+
+  Ok(install_dir)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn fetch_and_extract(
+  host: &dyn Host,
+  product: &Product,
+  version: &str,
+  artifact: &Artifact,
+  archive: &Path,
+  temp_dir: &Path,
+  binary_name: &str,
+  timeout: Option<Duration>,
+  pinned_sha256: Option<&str>,
+  allow_downloads: bool,
+  (os, arch): (&str, &str),
+) -> Result<()> {
+  let sha256 = match artifact {
+    | Artifact::Remote(url) => download(
+      host,
+      product,
+      version,
+      url,
+      archive,
+      timeout,
+      allow_downloads,
+    )?,
+    | Artifact::Local(path) => copy_local_artifact(host, path, archive)?,
+  };
+
+  // Checked before extracting, so nothing from a mismatching archive is ever
+  // written where it could be run.
+  let mut expected = vec![];
+  if let Artifact::Remote(url) = artifact {
+    expected.push(published_sha256(host, product, version, url, archive)?);
+  }
+  if let Some(pinned) = pinned_sha256 {
+    expected.push((string!("`volt.terraformlsSha256`"), string!(pinned)));
+  }
+  if let Some((expected_by, expected)) = expected.into_iter().find(|v| v.1 != sha256) {
+    return Err(
+      PluginError::ChecksumMismatch {
+        product: product.name,
+        source: string!(artifact.source()),
+        expected_by,
+        expected,
+        actual: sha256,
+      }
+      .into(),
+    );
+  }
+
+  // Downloads are always archives, a local artifact may also be the binary
+  // itself.
+  let kind = archive_kind(host, archive)?;
+  if let Some(kind) = kind {
+    info!(format!("Extracting {}", archive.display()));
+    log::timed("extraction", || match kind {
+      | ArchiveKind::Zip => extract(host, archive, temp_dir, binary_name),
+      | ArchiveKind::Tarball => extract_tarball(host, archive, temp_dir, binary_name),
+    })?;
+    debug!(format!("Extracted into {}", temp_dir.display()));
+  } else {
+    host.rename(archive, &temp_dir.join(binary_name))?;
+  }
+
+  if !host.exists(&temp_dir.join(binary_name)) {
+    return Err(
+      PluginError::MissingBinary {
+        product: product.name,
+        version: string!(version),
+        source: string!(artifact.source()),
+      }
+      .into(),
+    );
+  }
+
+  write_manifest(
+    host,
+    temp_dir,
+    &InstallManifest {
+      version: string!(version),
+      sha256,
+      url: string!(artifact.source()),
+      timestamp: SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs(),
+      os: string!(os),
+      arch: string!(arch),
+    },
+  )
+}
+
+// The checksums are next to the archives on the releases host as well as on
+// GitHub and mirrors of either.
+fn checksums_url(product: &Product, version: &str, download_url: &str) -> String {
+  let dir = download_url.rsplit_once('/').map_or(download_url, |v| v.0);
+  format!(
+    "{dir}/{}",
+    product.checksums_template.replace("{version}", version)
+  )
+}
+
+// The SHA256 the release lists for `archive`, along with where it's listed.
+fn published_sha256(
+  host: &dyn Host,
+  product: &Product,
+  version: &str,
+  download_url: &str,
+  archive: &Path,
+) -> Result<(String, String)> {
+  let url = checksums_url(product, version, download_url);
+  let failed = |reason: String| PluginError::DownloadFailed {
+    product: product.name,
+    version: string!(version),
+    url: url.clone(),
+    reason,
+  };
+  let mut resp = host.get(&url)?;
+  debug!(format!("GET {url}: {}", resp.status()));
+  if !(200..300).contains(&resp.status()) {
+    return Err(failed(format!("HTTP {}", resp.status())).into());
+  }
+
+  // `<sha256>  <name>`, with a `*` before the name for binary mode.
+  let sums = String::from_utf8_lossy(&resp.read_all()?).into_owned();
+  let name = archive.to_string_lossy();
+  sums
+    .lines()
+    .filter_map(|v| v.split_once(char::is_whitespace))
+    .find(|(_, v)| v.trim_start().trim_start_matches('*') == name)
+    .map(|(sha256, _)| (url.clone(), sha256.to_ascii_lowercase()))
+    .ok_or_else(|| failed(format!("{name} is not listed")).into())
+}
+
+// Asks before the first download of `product` what is going to be downloaded
+// from where, and remembers the answer for its later downloads. Dismissing the
+// prompt only declines this one.
+fn ask_consent(host: &dyn Host, product: &Product, version: &str, url: &str) -> Result<()> {
+  let declined = || PluginError::DownloadDeclined {
+    product: product.name,
+    version: string!(version),
+  };
+  match PersistedState::load(host)
+    .download_consents
+    .get(product.name)
+  {
+    | Some(true) => return Ok(()),
+    | Some(false) => return Err(declined().into()),
+    | None => {}
+  }
+
+  let size = match host.content_length(url) {
+    | Some(v) => format!("{:.1} MB", v as f64 / 1_000_000.0),
+    | None => string!("unknown size"),
+  };
+  let action = |title: &str| MessageActionItem {
+    title: string!(title),
+    properties: HashMap::new(),
+  };
+  let picked: Result<Option<MessageActionItem>, _> = PLUGIN_RPC.host_request(
+    ShowMessageRequest::METHOD,
+    ShowMessageRequestParams {
+      typ: MessageType::INFO,
+      message: format!(
+        "The plugin needs to download {name} {version} ({size}) from {url}. Allow it to download \
+         {name} releases?",
+        name = product.name
+      ),
+      actions: Some(vec![action(ALLOW_DOWNLOADS), action(DECLINE_DOWNLOADS)]),
+    },
+  );
+  // Without an answer `volt.allowDownloads` is the only way to allow them,
+  // which the hint of the error points to.
+  let picked = match picked {
+    | Ok(v) => v,
+    | Err(e) => {
+      debug!(format!("Failed to ask for consent to download: {e}"));
+      return Err(declined().into());
+    }
+  };
+  let consent = match picked.as_ref().map(|v| v.title.as_str()) {
+    | Some(ALLOW_DOWNLOADS) => true,
+    | Some(_) => false,
+    | None => return Err(declined().into()),
+  };
+  PersistedState::update(host, |v| {
+    v.download_consents.insert(string!(product.name), consent);
+  })?;
+  match consent {
+    | true => Ok(()),
+    | false => Err(declined().into()),
+  }
+}
+
+// The host doesn't support timeouts on requests, so only reading the body is
+// bounded, which is where a stalled connection usually hangs.
+fn download(
+  host: &dyn Host,
+  product: &Product,
+  version: &str,
+  download_url: &str,
+  archive: &Path,
+  timeout: Option<Duration>,
+  allow_downloads: bool,
+) -> Result<String> {
+  // Asked before the request, which already fetches the whole body when it
+  // goes through curl.
+  if !allow_downloads {
+    ask_consent(host, product, version, download_url)?;
+  }
+
+  let deadline = timeout.map(|v| Instant::now() + v);
+  let mut resp = host.get(download_url)?;
+  debug!(format!("GET {download_url}: {}", resp.status()));
+  match resp.status() {
+    | 403 | 404 => {
+      return Err(
+        PluginError::VersionNotFound {
+          product: product.name,
+          version: string!(version),
+          url: string!(download_url),
+        }
+        .into(),
+      )
+    }
+    | v if !(200..300).contains(&v) => {
+      return Err(
+        PluginError::DownloadFailed {
+          product: product.name,
+          version: string!(version),
+          url: string!(download_url),
+          reason: format!("HTTP {v}"),
+        }
+        .into(),
+      )
+    }
+    | _ => {}
+  }
+
+  let Some(sha256) = stream_to_file(host, resp.as_mut(), archive, deadline)? else {
+    return Err(
+      PluginError::DownloadTimedOut {
+        product: product.name,
+        version: string!(version),
+        timeout: timeout.unwrap_or_default(),
+      }
+      .into(),
+    );
+  };
+
+  if archive_kind(host, archive)?.is_none() {
+    return Err(
+      PluginError::NotAnArchive {
+        url: string!(download_url),
+      }
+      .into(),
+    );
+  }
+
+  Ok(sha256)
+}
+
+// The sandbox only has access to the volt directory, so the artifact is
+// copied in by the host first.
+fn copy_local_artifact(host: &dyn Host, path: &str, dest: &Path) -> Result<String> {
+  let host_dest = host_path(&volt_file_uri(host, dest)?);
+  let (program, args) = match host.operating_system().as_deref() {
+    | Ok("windows") => (
+      "cmd",
+      vec![string!("/C"), format!("copy /Y \"{path}\" \"{host_dest}\"")],
+    ),
+    | _ => ("cp", vec![string!("--"), string!(path), host_dest]),
+  };
+
+  match host.execute(program, args) {
+    | Some(_) => log::timed("checksum", || sha256_file(host, dest)),
+    | None => Err(
+      PluginError::LocalArchive {
+        path: string!(path),
+      }
+      .into(),
+    ),
+  }
+}
+
+// The zip crate is all the sandbox has, so tarballs are extracted by `tar` on
+// the host, which also ships with Windows 10 and later. Like with zip archives
+// only the binary is extracted, and `tar` refuses names that leave the target.
+fn extract_tarball(
+  host: &dyn Host,
+  archive: &Path,
+  temp_dir: &Path,
+  binary_name: &str,
+) -> Result<()> {
+  let archive_path = server::long_path(host, host_path(&volt_file_uri(host, archive)?));
+  let temp_dir_path = server::long_path(host, host_path(&volt_file_uri(host, temp_dir)?));
+  match host.execute(
+    "tar",
+    vec![
+      string!("-xzf"),
+      archive_path.clone(),
+      string!("-C"),
+      temp_dir_path,
+      string!(binary_name),
+    ],
+  ) {
+    | Some(_) => Ok(()),
+    | None => Err(anyhow!("Failed to extract {archive_path} with tar")),
+  }
+}
+
+// `enclosed_name` accepts some names that are absolute or escape the target
+// on Windows, so these are checked on the raw name as well.
+fn is_enclosed(name: &str) -> bool {
+  !name.starts_with(['/', '\\'])
+    && !name.contains(':')
+    && !name.split(['/', '\\']).any(|v| v == "..")
+}
+
+// Archives are untrusted and the sandbox can write anywhere in the volt
+// directory, so only the binary is extracted, from the top level of the
+// archive and up to `MAX_EXTRACTED_SIZE`. Everything else is skipped.
+fn extract(host: &dyn Host, archive: &Path, temp_dir: &Path, binary_name: &str) -> Result<()> {
+  let mut zip = ZipArchive::new(BufReader::new(host.open(archive)?))?;
+  let mut extracted = 0;
+
+  for i in 0..zip.len() {
+    let mut file = zip.by_index(i)?;
+    let name = string!(file.name());
+    let is_symlink = file.unix_mode().is_some_and(|v| v & 0o170000 == 0o120000);
+    // Entries that would be dangerous to extract are logged as such, the
+    // rest is just not needed.
+    let skipped = match () {
+      | _ if !is_enclosed(&name) || file.enclosed_name().is_none() => {
+        Some((true, "its path leaves the archive"))
+      }
+      | _ if is_symlink => Some((true, "it is a symlink")),
+      | _ if file.is_dir() => Some((false, "it is a directory")),
+      | _ if name != binary_name => Some((false, "only the binary is needed")),
+      | _ => None,
+    };
+    match skipped {
+      | Some((true, reason)) => {
+        info!(format!("Skipped {name} in {}: {reason}", archive.display()));
+        continue;
+      }
+      | Some((false, reason)) => {
+        debug!(format!("Skipped {name} in {}: {reason}", archive.display()));
+        continue;
+      }
+      | None => {}
+    }
+
+    let limit = MAX_EXTRACTED_SIZE - extracted;
+    let mut outfile = BufWriter::new(host.create(&temp_dir.join(binary_name))?);
+    let copied = io::copy(&mut file.by_ref().take(limit + 1), &mut outfile)?;
+    outfile.flush()?;
+    if copied > limit {
+      return Err(anyhow!(
+        "{} extracts to more than {}MiB",
+        archive.display(),
+        MAX_EXTRACTED_SIZE / 1024 / 1024
+      ));
+    }
+    extracted += copied;
+  }
+
+  Ok(())
+}
+
+// Removes every installed version along with leftover download artifacts.
+pub fn remove_installs(host: &dyn Host, product: &Product) -> Result<()> {
+  let install_root = Path::new(product.name);
+  if host.is_dir(install_root) {
+    stop_servers_in(host, install_root)?;
+    retry(&format!("remove {}", install_root.display()), || {
+      host.remove_dir_all(install_root)
+    })?;
+  }
+
+  for path in host.read_dir(Path::new("."))? {
+    if is_artifact(product, &path) {
+      host.remove_file(&path)?;
+    }
+  }
+
+  Ok(())
+}
+
+// Removes download artifacts and partially extracted versions left behind by
+// an interrupted install. Skipped while any instance holds an install lock, as
+// they may still be in use then. Returns how many were removed.
+pub fn remove_temp_artifacts(host: &dyn Host, product: &Product) -> Result<usize> {
+  let install_root = Path::new(product.name);
+  if is_installing(host, product) {
+    return Ok(0);
+  }
+
+  let mut removed = 0;
+  if host.is_dir(install_root) {
+    for path in host.read_dir(install_root)? {
+      if path.extension().and_then(|v| v.to_str()) == Some("partial") && host.is_dir(&path) {
+        host.remove_dir_all(&path)?;
+        removed += 1;
+      }
+    }
+  }
+
+  for path in host.read_dir(Path::new("."))? {
+    if is_artifact(product, &path) {
+      host.remove_file(&path)?;
+      removed += 1;
+    }
+  }
+
+  Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Cursor;
+
+  use serde_json::json;
+  use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+  use super::*;
+  use crate::{host::FileSystem, mock::MockHost};
+
+  const URL: &str =
+    "https://releases.hashicorp.com/terraform-ls/0.32.7/terraform-ls_0.32.7_linux_amd64.zip";
+  const SUMS_URL: &str =
+    "https://releases.hashicorp.com/terraform-ls/0.32.7/terraform-ls_0.32.7_SHA256SUMS";
+
+  fn zip(entries: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut zip = ZipWriter::new(Cursor::new(vec![]));
+    for (name, contents) in entries {
+      let options = FileOptions::default().compression_method(CompressionMethod::Stored);
+      zip.start_file(*name, options).unwrap();
+      zip.write_all(contents).unwrap();
+    }
+    zip.finish().unwrap().into_inner()
+  }
+
+  // Publishes the archives of 0.32.7 for Linux by architecture, along with
+  // their checksums.
+  fn release(host: &mut MockHost, archives: &[(&str, Vec<u8>)]) {
+    let mut sums = String::new();
+    for (arch, archive) in archives {
+      let name = format!("terraform-ls_0.32.7_linux_{arch}.zip");
+      sums += &format!("{}  {name}\n", hex(&Sha256::digest(archive)));
+      host.respond(&URL.replace("amd64", arch), 200, archive);
+    }
+    host.respond(SUMS_URL, 200, sums.as_bytes());
+  }
+
+  fn config(volt: Value) -> Config {
+    Config::parse(Some(&json!({ "volt": volt }))).unwrap()
+  }
+
+  fn fetch(host: &MockHost, url: &str, pinned_sha256: Option<&str>) -> Result<()> {
+    let temp_dir = Path::new("terraform-ls/0.32.7.partial");
+    host.create_dir_all(temp_dir)?;
+    fetch_and_extract(
+      host,
+      &TERRAFORM_LS,
+      "0.32.7",
+      &Artifact::Remote(string!(url)),
+      Path::new("terraform-ls_0.32.7_linux_amd64.zip"),
+      temp_dir,
+      "terraform-ls",
+      None,
+      pinned_sha256,
+      true,
+      ("linux", "amd64"),
+    )
+  }
+
+  #[test]
+  fn versions_are_ordered_by_semver() {
+    let v = |v| Version::parse(v).unwrap();
+    assert!(v("0.10.0") > v("0.9.12"));
+    assert!(v("0.32.7") > v("0.32.7-beta1"));
+    assert!(v("1.0.0-beta") > v("1.0.0-alpha"));
+    assert!(v("0.33.0-beta10") > v("0.33.0-beta2"));
+    assert!(v("1.0.0-rc.10") > v("1.0.0-rc.2"));
+    assert!(v("1.0.0-alpha.1") > v("1.0.0-alpha"));
+    assert!(v("1.0.0-alpha.beta") > v("1.0.0-alpha.1"));
+    assert!(v("v0.32.7") == v("0.32.7"));
+    assert_eq!(v("0.32.7-rc.1").to_string(), "0.32.7-rc.1");
+    assert!(Version::parse("0.32").is_none());
+    assert!(Version::parse("0.32.7.1").is_none());
+    assert!(Version::parse("latest").is_none());
+  }
+
+  #[test]
+  fn maps_the_platform_onto_release_names() {
+    let mut host = MockHost::new();
+    assert_eq!(platform(&host).unwrap(), Ok(("linux", "amd64")));
+    (host.os, host.arch) = ("macos", "aarch64");
+    assert_eq!(platform(&host).unwrap(), Ok(("darwin", "arm64")));
+    (host.os, host.arch) = ("linux", "arm");
+    assert_eq!(platform(&host).unwrap(), Ok(("linux", "arm")));
+
+    // There are no releases for it, so the server is looked up on PATH.
+    host.arch = "riscv64";
+    assert!(platform(&host).unwrap().is_err());
+  }
+
+  #[test]
+  fn installs_the_binary_and_reuses_it() {
+    let mut host = MockHost::new();
+    release(
+      &mut host,
+      &[(
+        "amd64",
+        zip(&[("LICENSE.txt", b"MPL"), ("terraform-ls", b"binary")]),
+      )],
+    );
+    let config = config(json!({ "allowDownloads": true }));
+
+    let (path, fresh) =
+      ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+    assert_eq!(path, Path::new("terraform-ls/0.32.7/terraform-ls"));
+    assert!(fresh);
+    assert_eq!(host.read(&path).unwrap(), b"binary");
+    assert!(!host.exists(Path::new("terraform-ls/0.32.7/LICENSE.txt")));
+    assert!(!host.exists(Path::new("terraform-ls_0.32.7_linux_amd64.zip")));
+
+    let (path, fresh) =
+      ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+    assert_eq!(path, Path::new("terraform-ls/0.32.7/terraform-ls"));
+    assert!(!fresh);
+    assert_eq!(host.requests.borrow().len(), 2);
+  }
+
+  #[test]
+  fn falls_back_to_the_installed_version() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", zip(&[("terraform-ls", b"binary")]))]);
+    let config = config(json!({ "allowDownloads": true }));
+    ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+
+    let (path, fresh) =
+      ensure_installed(&host, &config, "0.32.8", "linux", "amd64", "terraform-ls").unwrap();
+    assert_eq!(path, Path::new("terraform-ls/0.32.7/terraform-ls"));
+    assert!(!fresh);
+    assert!(!host.exists(Path::new("terraform-ls/0.32.8.partial")));
+  }
+
+  #[test]
+  fn reinstalls_for_another_platform() {
+    let mut host = MockHost::new();
+    release(
+      &mut host,
+      &[
+        ("amd64", zip(&[("terraform-ls", b"amd64")])),
+        ("arm64", zip(&[("terraform-ls", b"arm64")])),
+      ],
+    );
+    let config = config(json!({ "allowDownloads": true }));
+    ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+
+    let (path, fresh) =
+      ensure_installed(&host, &config, "0.32.7", "linux", "arm64", "terraform-ls").unwrap();
+    assert!(fresh);
+    assert_eq!(host.read(&path).unwrap(), b"arm64");
+  }
+
+  #[test]
+  fn keeps_the_versions_pinned_in_other_windows() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", zip(&[("terraform-ls", b"binary")]))]);
+    let config = config(json!({ "allowDownloads": true, "keepVersions": 1 }));
+    for (i, version) in ["0.32.4", "0.32.5", "0.32.6"].into_iter().enumerate() {
+      let dir = Path::new("terraform-ls").join(version);
+      host.create_dir_all(&dir).unwrap();
+      host.write(&dir.join("terraform-ls"), b"binary").unwrap();
+      let manifest = InstallManifest {
+        version: string!(version),
+        sha256: String::new(),
+        url: String::new(),
+        timestamp: i as u64,
+        os: string!("linux"),
+        arch: string!("amd64"),
+      };
+      write_manifest(&host, &dir, &manifest).unwrap();
+      record_start(&host, &TERRAFORM_LS, version, version != "0.32.4").unwrap();
+    }
+    // Started long enough ago that only the pins keep them.
+    let mut installed = read_installed_versions(&host, &TERRAFORM_LS);
+    installed
+      .started
+      .values_mut()
+      .for_each(|v| *v -= 2 * IN_USE.as_secs());
+    write_installed_versions(&host, &TERRAFORM_LS, &installed).unwrap();
+
+    let (_, fresh) =
+      ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+    assert!(fresh);
+    assert!(!host.exists(Path::new("terraform-ls/0.32.4")));
+    assert!(host.exists(Path::new("terraform-ls/0.32.5/terraform-ls")));
+    assert!(host.exists(Path::new("terraform-ls/0.32.6/terraform-ls")));
+
+    // Starting a pinned version again leaves the others alone.
+    let (path, fresh) =
+      ensure_installed(&host, &config, "0.32.6", "linux", "amd64", "terraform-ls").unwrap();
+    assert_eq!(path, Path::new("terraform-ls/0.32.6/terraform-ls"));
+    assert!(!fresh);
+    assert!(host.exists(Path::new("terraform-ls/0.32.5/terraform-ls")));
+    assert!(host.exists(Path::new("terraform-ls/0.32.7/terraform-ls")));
+  }
+
+  #[test]
+  fn rejects_a_mismatching_pinned_checksum() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", zip(&[("terraform-ls", b"binary")]))]);
+
+    let e = fetch(&host, URL, Some(&"0".repeat(64))).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::ChecksumMismatch { .. })
+    ));
+    assert!(!host.exists(Path::new("terraform-ls/0.32.7.partial/terraform-ls")));
+  }
+
+  #[test]
+  fn only_installs_the_pinned_archive() {
+    let mut host = MockHost::new();
+    let archive = zip(&[("terraform-ls", b"binary")]);
+    release(&mut host, &[("amd64", archive.clone())]);
+    ensure_installed(
+      &host,
+      &config(json!({ "allowDownloads": true })),
+      "0.32.7",
+      "linux",
+      "amd64",
+      "terraform-ls",
+    )
+    .unwrap();
+
+    // Listed in SHA256SUMS, but not pinned, so the install isn't used either.
+    let pinned = config(json!({ "allowDownloads": true, "terraformlsSha256": "0".repeat(64) }));
+    let e =
+      ensure_installed(&host, &pinned, "0.32.7", "linux", "amd64", "terraform-ls").unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::ChecksumMismatch { expected_by, .. }) if expected_by.contains("Sha256")
+    ));
+
+    let sha256 = hex(&Sha256::digest(&archive));
+    let pinned = config(json!({ "allowDownloads": true, "terraformlsSha256": sha256 }));
+    let (_, fresh) =
+      ensure_installed(&host, &pinned, "0.32.7", "linux", "amd64", "terraform-ls").unwrap();
+    assert!(!fresh);
+  }
+
+  #[test]
+  fn only_extracts_the_binary_from_the_top_level() {
+    let mut host = MockHost::new();
+    release(
+      &mut host,
+      &[(
+        "amd64",
+        zip(&[("../terraform-ls", b"binary"), ("bin/terraform-ls", b"")]),
+      )],
+    );
+
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::MissingBinary { .. })
+    ));
+    assert!(!host.exists(Path::new("terraform-ls/terraform-ls")));
+  }
+
+  #[test]
+  fn rejects_downloads_that_are_not_archives() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", b"<html>Sign in</html>".to_vec())]);
+
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::NotAnArchive { .. })
+    ));
+  }
+
+  #[test]
+  fn missing_releases_are_not_found() {
+    let host = MockHost::new();
+
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::VersionNotFound { .. })
+    ));
+  }
+
+  #[test]
+  fn rejects_archives_that_do_not_match_the_release_checksums() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", zip(&[("terraform-ls", b"binary")]))]);
+    host.respond(URL, 200, &zip(&[("terraform-ls", b"tampered")]));
+
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::ChecksumMismatch { expected_by, .. }) if expected_by == SUMS_URL
+    ));
+    assert!(!host.exists(Path::new("terraform-ls/0.32.7.partial/terraform-ls")));
+  }
+
+  #[test]
+  fn requires_the_release_checksums() {
+    let mut host = MockHost::new();
+    host.respond(URL, 200, &zip(&[("terraform-ls", b"binary")]));
+
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::DownloadFailed { url, .. }) if url == SUMS_URL
+    ));
+
+    host.respond(
+      SUMS_URL,
+      200,
+      b"0000  terraform-ls_0.32.7_darwin_arm64.zip\n",
+    );
+    let e = fetch(&host, URL, None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::DownloadFailed { url, .. }) if url == SUMS_URL
+    ));
+  }
+
+  #[test]
+  fn asks_before_downloading_each_product() {
+    let mut host = MockHost::new();
+    release(&mut host, &[("amd64", zip(&[("terraform-ls", b"binary")]))]);
+    let config = config(json!({}));
+    let install = || ensure_installed(&host, &config, "0.32.7", "linux", "amd64", "terraform-ls");
+
+    PersistedState::update(&host, |v| {
+      v.download_consents.insert(string!("trivy"), true);
+      v.download_consents.insert(string!("terraform-ls"), false);
+    })
+    .unwrap();
+    let e = install().unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::DownloadDeclined { .. })
+    ));
+    assert!(host.requests.borrow().is_empty());
+
+    // Nobody is there to answer the prompt outside of Lapce, which is the
+    // same as declining without remembering it.
+    PersistedState::update(&host, |v| v.download_consents.clear()).unwrap();
+    let e = install().unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::DownloadDeclined { .. })
+    ));
+    assert!(PersistedState::load(&host).download_consents.is_empty());
+    assert_eq!(*host.requests.borrow(), [format!("HEAD {URL}")]);
+
+    PersistedState::update(&host, |v| {
+      v.download_consents.insert(string!("terraform-ls"), true);
+    })
+    .unwrap();
+    assert!(install().is_ok());
+  }
+}
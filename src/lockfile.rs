@@ -0,0 +1,190 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{MessageType, Url};
+use serde::Deserialize;
+
+use crate::{
+  host::Host,
+  install::Version,
+  rpc::PLUGIN_RPC,
+  server::{host_path, read_host_file},
+};
+
+pub const LOCK_FILE: &str = ".terraform.lock.hcl";
+
+pub struct LockedProvider {
+  // e.g. `registry.terraform.io/hashicorp/aws`
+  pub address: String,
+  pub version: String,
+  pub constraints: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ProviderVersions {
+  versions: Vec<ProviderVersion>,
+}
+
+#[derive(Deserialize)]
+struct ProviderVersion {
+  version: String,
+}
+
+fn quoted(v: &str) -> Option<&str> {
+  v.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+// Only the top level `provider` blocks and their `version` and `constraints`
+// are needed, which `terraform init` always writes one per line.
+fn parse(contents: &str) -> Vec<LockedProvider> {
+  let mut providers = vec![];
+  let mut current: Option<LockedProvider> = None;
+
+  for line in contents.lines() {
+    let line = line.trim();
+    if let Some(rest) = line.strip_prefix("provider ") {
+      let address = rest.trim_end_matches('{').trim();
+      current = quoted(address).map(|address| LockedProvider {
+        address: string!(address),
+        version: String::new(),
+        constraints: None,
+      });
+      continue;
+    }
+    let Some(provider) = current.as_mut() else {
+      continue;
+    };
+    if line == "}" {
+      providers.extend(current.take());
+      continue;
+    }
+    let Some((key, value)) = line.split_once('=') else {
+      continue;
+    };
+    match key.trim() {
+      | "version" => provider.version = string!(quoted(value).unwrap_or_default()),
+      | "constraints" => provider.constraints = quoted(value).map(String::from),
+      | _ => {}
+    }
+  }
+
+  providers
+}
+
+// Both registries serve the provider registry protocol under the same path.
+fn latest_version(host: &dyn Host, address: &str) -> Result<Option<Version>> {
+  let mut parts = address.split('/');
+  let (Some(registry), Some(namespace), Some(name), None) =
+    (parts.next(), parts.next(), parts.next(), parts.next())
+  else {
+    return Err(anyhow!("Unexpected provider address {address}"));
+  };
+  let url = format!("https://{registry}/v1/providers/{namespace}/{name}/versions");
+  let mut resp = host.get(&url)?;
+  if !(200..300).contains(&resp.status()) {
+    return Err(anyhow!(
+      "Failed to look up {address} at {url}: HTTP {}",
+      resp.status()
+    ));
+  }
+  let versions: ProviderVersions = serde_json::from_slice(&resp.read_all()?)?;
+  Ok(
+    versions
+      .versions
+      .iter()
+      .filter_map(|v| Version::parse(&v.version))
+      .filter(|v| !v.is_prerelease())
+      .max(),
+  )
+}
+
+pub fn read(host: &dyn Host, root_uri: &Url) -> Option<Vec<LockedProvider>> {
+  let path = host_path(&root_uri.join(LOCK_FILE).ok()?);
+  Some(parse(&read_host_file(host, &path)?))
+}
+
+// Logs every locked provider with the newest release it could be updated to
+// and shows how many are behind.
+pub fn inspect(host: &dyn Host, root_uri: &Url) -> Result<()> {
+  let Some(providers) = read(host, root_uri) else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("There is no {LOCK_FILE}, run `terraform init` to create it"),
+    )?;
+    return Ok(());
+  };
+
+  let mut outdated = 0;
+  for provider in &providers {
+    let constraints = match &provider.constraints {
+      | Some(v) => format!(", constraints {v}"),
+      | None => String::new(),
+    };
+    let latest = match latest_version(host, &provider.address) {
+      | Ok(Some(latest)) if Version::parse(&provider.version).is_some_and(|v| latest > v) => {
+        outdated += 1;
+        format!(", {latest} is available")
+      }
+      | Ok(_) => string!(", up to date"),
+      | Err(e) => format!(", {e}"),
+    };
+    info!(format!(
+      "{LOCK_FILE}: {} {}{constraints}{latest}",
+      provider.address, provider.version
+    ));
+  }
+
+  let summary = match (providers.len(), outdated) {
+    | (0, _) => format!("{LOCK_FILE} locks no providers"),
+    | (locked, 0) => format!("{locked} providers are locked, all on their latest release"),
+    | (locked, outdated) => format!(
+      "{locked} providers are locked, {outdated} have newer releases, see the log for details"
+    ),
+  };
+  PLUGIN_RPC.window_show_message(MessageType::INFO, summary)?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn parses_the_locked_providers() {
+    let providers = parse(
+      r#"# This file is maintained automatically by "terraform init".
+
+provider "registry.terraform.io/hashicorp/aws" {
+  version     = "5.31.0"
+  constraints = "~> 5.0"
+  hashes = [
+    "h1:abc=",
+  ]
+}
+
+provider "registry.terraform.io/hashicorp/random" {
+  version = "3.6.0"
+}
+"#,
+    );
+    let providers: Vec<_> = providers
+      .iter()
+      .map(|v| {
+        (
+          v.address.as_str(),
+          v.version.as_str(),
+          v.constraints.as_deref(),
+        )
+      })
+      .collect();
+    assert_eq!(
+      providers,
+      [
+        (
+          "registry.terraform.io/hashicorp/aws",
+          "5.31.0",
+          Some("~> 5.0")
+        ),
+        ("registry.terraform.io/hashicorp/random", "3.6.0", None),
+      ]
+    );
+  }
+}
@@ -0,0 +1,111 @@
+use std::{
+  fs::{self, OpenOptions},
+  io::Write,
+  sync::atomic::{AtomicU8, Ordering},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+// Activity of the plugin itself, the language server logs through Lapce.
+const LOG_FILE: &str = "plugin.log";
+const ROTATED_LOG_FILE: &str = "plugin.log.1";
+const MAX_LOG_SIZE: u64 = 1024 * 1024;
+
+// The tests run from the repository, which the log file has no place in.
+static LEVEL: AtomicU8 = AtomicU8::new(match cfg!(test) {
+  | true => Level::Off as u8,
+  | false => Level::Info as u8,
+});
+
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Off,
+  Error,
+  Warn,
+  Info,
+  Debug,
+}
+
+impl Level {
+  pub fn parse(v: &str) -> Option<Level> {
+    match v {
+      | "off" => Some(Level::Off),
+      | "error" => Some(Level::Error),
+      | "warn" => Some(Level::Warn),
+      | "info" => Some(Level::Info),
+      | "debug" => Some(Level::Debug),
+      | _ => None,
+    }
+  }
+
+  fn name(&self) -> &'static str {
+    match self {
+      | Level::Off => "OFF",
+      | Level::Error => "ERROR",
+      | Level::Warn => "WARN",
+      | Level::Info => "INFO",
+      | Level::Debug => "DEBUG",
+    }
+  }
+}
+
+pub fn set_level(level: Level) {
+  LEVEL.store(level as u8, Ordering::Relaxed);
+}
+
+// Logging must never get in the way, so failing to write is ignored.
+pub fn write(level: Level, message: &str) {
+  if level == Level::Off || level as u8 > LEVEL.load(Ordering::Relaxed) {
+    return;
+  }
+
+  if fs::metadata(LOG_FILE).is_ok_and(|v| v.len() > MAX_LOG_SIZE) {
+    let _ = fs::rename(LOG_FILE, ROTATED_LOG_FILE);
+  }
+  let Ok(mut file) = OpenOptions::new().create(true).append(true).open(LOG_FILE) else {
+    return;
+  };
+  let _ = writeln!(file, "{} {:5} {message}", timestamp(), level.name());
+}
+
+// Logs how long `phase` took at debug level, to tell which part of starting
+// up is slow.
+pub fn timed<T>(phase: &str, f: impl FnOnce() -> T) -> T {
+  let started = Instant::now();
+  let result = f();
+  duration(phase, started.elapsed());
+  result
+}
+
+pub fn duration(phase: &str, duration: Duration) {
+  write(
+    Level::Debug,
+    &format!("Timing: {phase} took {:.3}s", duration.as_secs_f64()),
+  );
+}
+
+// `YYYY-MM-DDTHH:MM:SSZ`, there is no time zone database in the sandbox.
+fn timestamp() -> String {
+  let secs = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|v| v.as_secs())
+    .unwrap_or_default();
+  let (days, secs) = (secs / 86400, secs % 86400);
+
+  // Days to a civil date, from https://howardhinnant.github.io/date_algorithms.html
+  let z = days as i64 + 719468;
+  let era = z.div_euclid(146097);
+  let doe = z.rem_euclid(146097);
+  let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+  let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+  let mp = (5 * doy + 2) / 153;
+  let day = doy - (153 * mp + 2) / 5 + 1;
+  let month = if mp < 10 { mp + 3 } else { mp - 9 };
+  let year = yoe + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+    secs / 3600,
+    secs % 3600 / 60,
+    secs % 60
+  )
+}
@@ -1,36 +1,54 @@
-use std::{
-  fs::{self, File},
-  io,
-  path::PathBuf,
-};
+// Everything that is only reached from the plugin entry points is unused in
+// the tests.
+#![cfg_attr(not(target_os = "wasi"), allow(dead_code, unused_imports))]
+
+use std::{collections::HashMap, path::Path, time::Instant};
 
 use anyhow::{anyhow, Result};
-use lapce_plugin::{
-  psp_types::{
-    lsp_types::{
-      request::Initialize, DocumentFilter, DocumentSelector, InitializeParams, MessageType, Url,
-    },
-    Request,
+#[cfg(target_os = "wasi")]
+use lapce_plugin::{register_plugin, LapcePlugin};
+use psp_types::{
+  lsp_types::{
+    notification::{DidOpenTextDocument, DidSaveTextDocument, Exit, Initialized},
+    request::{Initialize, ShowMessageRequest, Shutdown},
+    DidOpenTextDocumentParams, DidSaveTextDocumentParams, InitializeParams, MessageActionItem,
+    MessageType, ShowMessageRequestParams, Url,
   },
-  register_plugin, Http, LapcePlugin, VoltEnvironment, PLUGIN_RPC,
+  Notification, Request,
 };
 use serde_json::Value;
-use zip::ZipArchive;
-
-#[derive(Default)]
-struct State {}
-
-register_plugin!(State);
 
 macro_rules! error {
-  ($x:expr) => {
-    PLUGIN_RPC.window_log_message(MessageType::ERROR, $x)?;
-  };
+  ($x:expr) => {{
+    let message = $x;
+    $crate::log::write($crate::log::Level::Error, &message);
+    $crate::rpc::PLUGIN_RPC
+      .window_log_message(::psp_types::lsp_types::MessageType::ERROR, message)?;
+  }};
 }
 
 macro_rules! info {
+  ($x:expr) => {{
+    let message = $x;
+    $crate::log::write($crate::log::Level::Info, &message);
+    $crate::rpc::PLUGIN_RPC
+      .window_log_message(::psp_types::lsp_types::MessageType::INFO, message)?;
+  }};
+}
+
+macro_rules! warn {
+  ($x:expr) => {{
+    let message = $x;
+    $crate::log::write($crate::log::Level::Warn, &message);
+    $crate::rpc::PLUGIN_RPC
+      .window_show_message(::psp_types::lsp_types::MessageType::WARNING, message)?;
+  }};
+}
+
+// Only goes to the plugin log file.
+macro_rules! debug {
   ($x:expr) => {
-    PLUGIN_RPC.window_log_message(MessageType::INFO, $x)?;
+    $crate::log::write($crate::log::Level::Debug, &$x)
   };
 }
 
@@ -40,165 +58,749 @@ macro_rules! string {
   };
 }
 
-const TERRAFORM_LS_VERSION: &str = "0.32.7";
+mod checkov;
+mod cli;
+mod config;
+mod diagnose;
+mod docs;
+mod error;
+mod hcl;
+mod host;
+mod ignore;
+mod infracost;
+mod install;
+mod lockfile;
+mod log;
+#[cfg(test)]
+mod mock;
+mod modules;
+mod prewarm;
+mod rpc;
+mod scanner;
+mod server;
+mod serverlog;
+mod state;
+mod supervisor;
+mod tools;
+mod trivy;
+mod updates;
+mod validate;
+mod variables;
+mod watcher;
 
-fn initialize(params: InitializeParams) -> Result<()> {
-  let document_selector: DocumentSelector = vec![
-    DocumentFilter {
-      language: Some(string!("terraform")),
-      pattern: Some(string!("**/*.tf")),
-      scheme: None,
-    },
-    DocumentFilter {
-      language: Some(string!("terraform")),
-      pattern: Some(string!("**/*.tfvars")),
-      scheme: None,
-    },
-  ];
-
-  let mut terraform_ls_version = string!(TERRAFORM_LS_VERSION);
-  let mut server_args = vec![string!("serve")];
-  let mut options = None;
-
-  if let Some(opts) = params.initialization_options.as_ref() {
-    options = opts.get("terraform-ls").map(|k| k.to_owned());
-
-    if let Some(volt) = opts.get("volt") {
-      if let Some(args) = volt.get("serverArgs") {
-        if let Some(args) = args.as_array() {
-          for arg in args {
-            if let Some(arg) = arg.as_str() {
-              server_args.push(string!(arg));
-            }
-          }
-        }
-      }
+use config::Config;
+use error::PluginError;
+use host::Host;
+#[cfg(target_os = "wasi")]
+use host::Lapce;
+use install::{Product, TERRAFORM_LS, TOFU_LS};
+use rpc::PLUGIN_RPC;
+use scanner::{Publisher, Scanners};
+use server::Launch;
+use state::PersistedState;
+use supervisor::Supervisor;
+use watcher::Watcher;
 
-      if let Some(server_path) = volt.get("serverPath") {
-        if let Some(server_path) = server_path.as_str() {
-          if !server_path.is_empty() {
-            let Ok(server_uri) = Url::parse(&format!("urn:{server_path}")) else {
-              return Err(anyhow!("Failed to parse URL"));
-            };
-            PLUGIN_RPC.start_lsp(server_uri, server_args, document_selector, options)?;
-            return Ok(());
-          }
-        }
-      }
+#[derive(Default)]
+struct State {
+  // Installing can take a while, so it only happens once the host has moved
+  // on from the `initialize` request.
+  pending_initialize: Option<InitializeParams>,
+  params: Option<InitializeParams>,
+  config: Option<Config>,
+  product: Option<&'static Product>,
+  server_uri: Option<Url>,
+  // The Terraform file opened or saved last, for commands that need one.
+  last_document: Option<Url>,
+  // Where the server logs to, with the timestamp filled in.
+  server_log: Option<String>,
+  // The installed version of the language server.
+  version: Option<String>,
+  // The directory the server was started from, under the product directory.
+  install_dir: Option<String>,
+  // A newer release the user was told about, for the update commands.
+  available_update: Option<String>,
+  lsp_started: bool,
+  root_uri: Option<Url>,
+  project_version: Option<String>,
+  // One per server, which is one per workspace folder with
+  // `volt.serverPerFolder`.
+  supervisors: Vec<Supervisor>,
+  watcher: Watcher,
+  scanners: Scanners,
+  publisher: Publisher,
+  shutting_down: bool,
+}
 
-      if let Some(tf_ls) = volt.get("terraformlsVersion") {
-        if let Some(tf_ls) = tf_ls.as_str() {
-          let tf_ls = tf_ls.trim();
-          if !tf_ls.is_empty() {
-            terraform_ls_version = string!(tf_ls)
-          }
-        }
-      }
-    }
+#[cfg(target_os = "wasi")]
+register_plugin!(State);
+
+// Only the tests run outside of Lapce.
+#[cfg(not(target_os = "wasi"))]
+fn main() {}
+
+const REINSTALL_COMMAND: &str = "terraform/reinstallLanguageServer";
+const DIAGNOSE_COMMAND: &str = "terraform/diagnoseSetup";
+const VALIDATE_COMMAND: &str = "terraform/validateWorkspace";
+const INSPECT_LOCK_FILE_COMMAND: &str = "terraform/inspectLockFile";
+const OPEN_SERVER_LOG_COMMAND: &str = "terraform/openServerLog";
+const TAIL_SERVER_LOG_COMMAND: &str = "terraform/tailServerLog";
+const PREFETCH_SCHEMAS_COMMAND: &str = "terraform/prefetchProviderSchemas";
+const OPEN_DOCS_COMMAND: &str = "terraform/openProviderDocs";
+const MODULE_SOURCE_COMMAND: &str = "terraform/goToModuleSource";
+const VARIABLES_COMMAND: &str = "terraform/reportVariableUsage";
+const SELECT_VERSION_COMMAND: &str = "terraform/selectLanguageServerVersion";
+const SNOOZE_UPDATES_COMMAND: &str = "terraform/snoozeUpdates";
+const SKIP_UPDATE_COMMAND: &str = "terraform/skipUpdate";
+const CLEAN_CACHE_COMMAND: &str = "terraform/cleanPluginCache";
+const ESTIMATE_COSTS_COMMAND: &str = "terraform/estimateCosts";
+// Every version is a button, so only the newest ones are offered.
+const VERSION_PICKER_LIMIT: usize = 10;
+const DIAGNOSTICS_FILE: &str = "diagnostics.txt";
+const PROJECT_VERSION_FILE: &str = ".terraform-ls-version";
+
+fn project_version(host: &dyn Host, root_uri: &Url) -> Option<String> {
+  let path = server::host_path(&root_uri.join(PROJECT_VERSION_FILE).ok()?);
+  let contents = server::read_host_file(host, &path)?;
+  let version = contents.lines().next()?.trim();
+  if version.is_empty() {
+    return None;
   }
+  Some(string!(version))
+}
 
-  let arch = match VoltEnvironment::architecture().as_deref() {
-    | Ok("x86") => "386",
-    | Ok("x86_64") => "amd64",
-    | Ok("aarch64") => "arm64",
-    | Ok(v) => return Err(anyhow!("Unsupported ARCH: {}", v)),
-    | Err(e) => return Err(anyhow!("Error ARCH: {}", e)),
+fn project_version_changed(state: &mut State, host: &dyn Host) -> Result<()> {
+  let Some(root_uri) = state.root_uri.as_ref() else {
+    return Ok(());
   };
+  let version = project_version(host, root_uri);
+  if version == state.project_version {
+    return Ok(());
+  }
 
-  let zip_file = match VoltEnvironment::operating_system().as_deref() {
-    | Ok("macos") => format!("terraform-ls_{terraform_ls_version}_darwin_{arch}.zip"),
-    | Ok("linux") => format!("terraform-ls_{terraform_ls_version}_linux_{arch}.zip"),
-    | Ok("windows") => format!("terraform-ls_{terraform_ls_version}_windows_{arch}.zip"),
-    | Ok("openbsd") => format!("terraform-ls_{terraform_ls_version}_openbsd_{arch}.zip"),
-    | Ok("freebsd") => format!("terraform-ls_{terraform_ls_version}_freebsd_{arch}.zip"),
-    | Ok(v) => return Err(anyhow!("Unsupported OS: {}", v)),
-    | Err(e) => return Err(anyhow!("Error OS: {}", e)),
+  let message = match &version {
+    | Some(v) => format!("{PROJECT_VERSION_FILE} now pins version {v}"),
+    | None => format!("{PROJECT_VERSION_FILE} no longer pins a version"),
   };
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!("{message}, reload the plugin to restart the language server"),
+  )?;
+  state.project_version = version;
+  Ok(())
+}
 
-  PLUGIN_RPC.stderr(&format!("ZIP_FILE: {}", zip_file));
+fn initialize(state: &mut State, host: &dyn Host, params: InitializeParams) -> Result<()> {
+  let started = Instant::now();
+  state.params = Some(params.clone());
 
-  let zip_file = PathBuf::from(zip_file);
+  let mut config = Config::parse(params.initialization_options.as_ref())?;
+  log::set_level(config.plugin_log_level);
+  let product = config.product;
+
+  // Only local folders count, a file opened on its own may come with
+  // neither a root nor workspace folders, or with an empty list of them.
+  state.root_uri = params
+    .root_uri
+    .clone()
+    .or_else(|| {
+      params
+        .workspace_folders
+        .as_ref()?
+        .first()
+        .map(|v| v.uri.clone())
+    })
+    .filter(|v| v.scheme() == "file")
+    // Files are looked up with `join`, which replaces the last segment unless
+    // it ends with a slash.
+    .map(|mut v| {
+      if !v.path().ends_with('/') {
+        let path = format!("{}/", v.path());
+        v.set_path(&path);
+      }
+      v
+    });
+  if state.root_uri.is_none() {
+    info!(format!(
+      "No workspace folder is open, starting {} in single file mode",
+      product.name
+    ));
+    config.lsp_options = Some(server::single_file_options(config.lsp_options));
+  }
 
-  let download_url = format!(
-    "https://releases.hashicorp.com/terraform-ls/{terraform_ls_version}/{}",
-    zip_file.display()
+  debug!(config.describe());
+  host::set_ca_bundle(
+    config
+      .ca_bundle_path
+      .clone()
+      .or_else(|| server::host_env(host, "SSL_CERT_FILE")),
   );
+  state.config = Some(config.clone());
+  // The new scanners don't know what the previous ones published.
+  state.scanners.clear(&mut state.publisher)?;
+  state.scanners = Scanners::new(&config);
 
-  let server_path = match VoltEnvironment::operating_system().as_deref() {
-    | Ok("windows") => PathBuf::from("terraform-ls.exe"),
-    | _ => PathBuf::from("terraform-ls"),
+  if let Some(server_path) = &config.server_path {
+    match server::resolve_server_path(host, server_path, state.root_uri.as_ref())
+      .and_then(|v| server::validate_server_path(host, &v).map(|_| v))
+    {
+      | Ok(resolved) => {
+        let Ok(server_uri) = Url::parse(&format!("urn:{resolved}")) else {
+          return Err(anyhow!("Failed to parse URL"));
+        };
+        return start(state, host, &config, server_uri);
+      }
+      | Err(e) => {
+        warn!(format!("{e}, falling back to the bundled {}", product.name));
+      }
+    }
+  }
+
+  state.project_version = state
+    .root_uri
+    .as_ref()
+    .and_then(|v| project_version(host, v));
+  let pinned_version = state
+    .project_version
+    .clone()
+    .or(config.version.clone())
+    .or_else(|| {
+      PersistedState::load(host)
+        .selected_versions
+        .remove(product.name)
+    });
+  let pinned = pinned_version.is_some();
+  let binary_name = install::binary_name(host, product);
+  let version = log::timed("version resolution", || {
+    install::resolve_version(host, &config, pinned_version, &binary_name)
+  })?;
+
+  // There are no release builds for this platform, but one may have been
+  // installed through other means.
+  let (os, arch) = match install::platform(host)? {
+    | Ok(v) => v,
+    | Err(reason) => {
+      if !server::can_execute(host, product.name) {
+        return Err(
+          PluginError::UnsupportedPlatform {
+            product: product.name,
+            reason,
+          }
+          .into(),
+        );
+      }
+      info!(format!("{reason}, using {} from PATH", product.name));
+      let Ok(server_uri) = Url::parse(&format!("urn:{}", product.name)) else {
+        return Err(anyhow!("Failed to parse URL"));
+      };
+      return start(state, host, &config, server_uri);
+    }
   };
 
-  if !PathBuf::from(&server_path).exists() {
-    if zip_file.exists() {
-      fs::remove_file(&zip_file)?;
+  state.product = Some(product);
+  state.version = Some(version.clone());
+  // Leftovers of installs interrupted in earlier sessions.
+  install::remove_temp_artifacts(host, product)?;
+
+  debug!(format!(
+    "Resolved {} {version} for {os}/{arch}",
+    product.name
+  ));
+  let (server_path, fresh_install) = log::timed("install", || {
+    install::ensure_installed(host, &config, &version, os, arch, &binary_name)
+  })?;
+  debug!(format!(
+    "Using {} (fresh install: {fresh_install})",
+    server_path.display()
+  ));
+  state.install_dir = server_path
+    .parent()
+    .and_then(|v| v.file_name())
+    .and_then(|v| v.to_str())
+    .map(String::from);
+  if let Some(dir) = &state.install_dir {
+    // Keeps other windows from removing it while it runs.
+    install::record_start(host, product, dir, pinned)?;
+  }
+  let server_uri = server::volt_file_uri(host, &server_path)?;
+
+  if !matches!(host.operating_system().as_deref(), Ok("windows")) {
+    if fresh_install {
+      server::set_executable(host, &server_uri)?;
     }
-    let mut resp = Http::get(&download_url)?;
-    PLUGIN_RPC.stderr(&format!("STATUS_CODE: {:?}", resp.status_code));
-    if resp.status_code.is_success() {
-      let body = resp.body_read_all()?;
+    server::check_executable(host, &server_uri)?;
+  }
 
-      fs::write(&zip_file, body)?;
+  // There is no way to stop a running server from the plugin.
+  if state.lsp_started {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!(
+        "{} is installed, reload the plugin to restart the language server",
+        product.name
+      ),
+    )?;
+    return Ok(());
+  }
 
-      let mut zip = ZipArchive::new(File::open(&zip_file)?)?;
+  info!(format!("Starting LSP server with URI: {server_uri}"));
+  start(state, host, &config, server_uri)?;
+  log::duration("startup", started.elapsed());
 
-      for i in 0..zip.len() {
-        let mut file = zip.by_index(i)?;
-        let outpath = match file.enclosed_name() {
-          | Some(path) => path.to_owned(),
-          | None => continue,
-        };
+  // Versions pinned in the settings or the project were chosen on purpose.
+  let is_pinned = state.project_version.is_some() || config.version.is_some();
+  state.available_update = None;
+  if config.update_notifications && !is_pinned && config.local_archive.is_none() {
+    state.available_update = updates::check(host, &config, &version).unwrap_or_else(|e| {
+      debug!(format!("Failed to check for {} updates: {e}", product.name));
+      None
+    });
+  }
 
-        if (*file.name()).ends_with('/') {
-          fs::create_dir_all(&outpath)?;
-        } else {
-          if let Some(path) = outpath.parent() {
-            if !path.exists() {
-              fs::create_dir_all(path)?;
-            }
-          }
-          let mut outfile = File::create(&outpath)?;
-          io::copy(&mut file, &mut outfile)?;
-        }
-      }
-    }
+  Ok(())
+}
 
-    if let Err(e) = fs::remove_file(&zip_file) {
-      error!(format!(
-        "Failed to remove download artifact! L: {} C: {} e: {e}",
-        line!(),
-        column!()
+fn start(state: &mut State, host: &dyn Host, config: &Config, server_uri: Url) -> Result<()> {
+  if state.lsp_started {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!(
+        "Reload the plugin to restart {} with the changes",
+        config.product.name
+      ),
+    )?;
+    return Ok(());
+  }
+  state.server_uri = Some(server_uri.clone());
+  let mut server_args = config.server_args.clone();
+  state.server_log = config.server_log_file.as_deref().map(serverlog::log_file);
+  if let Some(log_file) = &state.server_log {
+    // Right after `serve`, flags after other arguments aren't parsed.
+    server_args.insert(1, format!("-log-file={log_file}"));
+  }
+  let debug_port = match (config.debug_port, server::is_windows(host)) {
+    | (Some(_), true) => {
+      warn!(string!("`volt.debugPort` is not supported on Windows"));
+      None
+    }
+    | (port, _) => port,
+  };
+  let folders: Vec<Url> = state
+    .params
+    .as_ref()
+    .and_then(|v| v.workspace_folders.as_ref())
+    .map(|v| {
+      v.iter()
+        .map(|v| v.uri.clone())
+        .filter(|v| v.scheme() == "file")
+        .collect()
+    })
+    .unwrap_or_default();
+  let per_folder = config.server_per_folder && folders.len() > 1;
+  // Only one server can listen on the port.
+  let debug_port = match (debug_port, per_folder) {
+    | (Some(_), true) => {
+      warn!(string!(
+        "`volt.debugPort` is ignored with `volt.serverPerFolder` and more than one folder"
+      ));
+      None
+    }
+    | (port, _) => port,
+  };
+  if let Some(port) = debug_port {
+    info!(format!("Serving {} on port {port}", config.product.name));
+    server_args.insert(1, format!("-port={port}"));
+  }
+  let launch = |document_selector, options| Launch {
+    server_uri: server_uri.clone(),
+    server_args: server_args.clone(),
+    env: server::server_env(host, config),
+    options,
+    document_selector,
+    debug_port,
+  };
+  let mut launches = vec![];
+  if per_folder {
+    for folder in &folders {
+      info!(format!(
+        "Starting {} for {}",
+        config.product.name,
+        server::host_path(folder)
       ));
-    };
+      launches.push(launch(
+        server::folder_document_selector(config, folder),
+        Some(server::folder_options(
+          config.lsp_options.clone(),
+          &folders,
+          folder,
+        )),
+      ));
+    }
+  } else {
+    launches.push(launch(
+      server::document_selector(config),
+      config.lsp_options.clone(),
+    ));
   }
+  state.supervisors = (0..launches.len()).map(Supervisor::new).collect();
+  for (supervisor, launch) in state.supervisors.iter_mut().zip(launches) {
+    log::timed("start_lsp", || {
+      supervisor.start(
+        host,
+        config.product.name,
+        launch,
+        config.max_restarts,
+        config.init_timeout,
+      )
+    })?;
+  }
+  state.lsp_started = true;
+  if let Some(root_uri) = &state.root_uri {
+    state.watcher.register(host, root_uri)?;
+  }
+  cli::check(host, config)?;
+  Ok(())
+}
+
+fn reinstall(state: &mut State, host: &dyn Host) -> Result<()> {
+  let (Some(product), Some(params)) = (state.product, state.params.clone()) else {
+    return Err(anyhow!(
+      "The language server wasn't installed by the plugin, nothing to reinstall"
+    ));
+  };
+
+  info!(format!("Reinstalling {}", product.name));
+  install::remove_installs(host, product)?;
+  initialize(state, host, params)
+}
 
-  let volt_uri = VoltEnvironment::uri()?;
+// Removes what interrupted installs left behind, of both language servers,
+// and versions beyond `volt.keepVersions` of the one in use.
+fn clean_cache(state: &State, host: &dyn Host) -> Result<()> {
+  let mut removed = 0;
+  for product in [&TERRAFORM_LS, &TOFU_LS] {
+    removed += install::remove_temp_artifacts(host, product)?;
+  }
+  if let (Some(product), Some(config), Some(current)) =
+    (state.product, &state.config, &state.install_dir)
+  {
+    removed += install::collect_garbage(host, product, current, config.keep_versions)?;
+  }
+  info!(format!(
+    "Removed {removed} files and directories from the plugin cache"
+  ));
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    match removed {
+      | 0 => string!("There was nothing to clean up"),
+      | n => format!("Removed {n} leftover downloads and old versions"),
+    },
+  )?;
+  Ok(())
+}
 
-  let server_path = match server_path.to_str() {
-    | Some(v) => v,
-    | None => return Err(anyhow!("server_path.to_str() failed")),
+fn select_version(state: &mut State, host: &dyn Host) -> Result<()> {
+  let (Some(product), Some(config), Some(params)) =
+    (state.product, state.config.clone(), state.params.clone())
+  else {
+    return Err(anyhow!(
+      "The language server wasn't installed by the plugin, there is no version to select"
+    ));
   };
-  let Ok(server_uri) = Url::parse(&volt_uri) else {
-    return Err(anyhow!("Failed to parse URL!"));
+
+  let current = state.version.clone();
+  let actions = install::list_versions(host, &config)?
+    .into_iter()
+    .take(VERSION_PICKER_LIMIT)
+    .map(|v| MessageActionItem {
+      title: match current.as_deref() == Some(v.as_str()) {
+        | true => format!("{v} (current)"),
+        | false => v,
+      },
+      properties: HashMap::new(),
+    })
+    .collect();
+  let picked: Option<MessageActionItem> = PLUGIN_RPC.host_request(
+    ShowMessageRequest::METHOD,
+    ShowMessageRequestParams {
+      typ: MessageType::INFO,
+      message: format!("Select the {} version to install", product.name),
+      actions: Some(actions),
+    },
+  )?;
+  let Some(picked) = picked else {
+    return Ok(());
   };
-  let server_uri = server_uri.join(server_path)?;
+  let version = picked.title.trim_end_matches(" (current)");
+  if current.as_deref() == Some(version) {
+    return Ok(());
+  }
 
-  info!(format!("Starting LSP server with URI: {server_uri}"));
-  PLUGIN_RPC.start_lsp(server_uri, server_args, document_selector, options)?;
+  PersistedState::update(host, |v| {
+    v.selected_versions
+      .insert(string!(product.name), string!(version));
+  })?;
+  if state.project_version.is_some() || config.version.is_some() {
+    warn!(format!(
+      "Selected {} {version}, but the version setting or {PROJECT_VERSION_FILE} takes precedence",
+      product.name
+    ));
+    return Ok(());
+  }
+  info!(format!("Selected {} {version}", product.name));
+  initialize(state, host, params)
+}
 
+fn diagnose(state: &State, host: &dyn Host) -> Result<()> {
+  let report = diagnose::report(state, host);
+  info!(report.clone());
+  host.write(Path::new(DIAGNOSTICS_FILE), report.as_bytes())?;
+  let path = server::host_path(&server::volt_file_uri(host, Path::new(DIAGNOSTICS_FILE))?);
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!("Diagnostics were written to the log and {path}, include them in bug reports"),
+  )?;
   Ok(())
 }
 
+// Lapce stops the server it started itself, what's left is making sure that
+// isn't taken for a crash and cleaning up after interrupted installs.
+fn shutdown(state: &mut State, host: &dyn Host) -> Result<()> {
+  if state.shutting_down {
+    return Ok(());
+  }
+  state.shutting_down = true;
+  for supervisor in &mut state.supervisors {
+    supervisor.stop(host);
+  }
+  if let Some(product) = state.product {
+    install::remove_temp_artifacts(host, product)?;
+  }
+  Ok(())
+}
+
+// Logs the full error and shows the user a short message with a suggested fix.
+fn report_error(e: &anyhow::Error) {
+  let message = match e.downcast_ref::<PluginError>() {
+    | Some(e) => format!("{} {}", e.summary(), e.hint()),
+    | None => e.to_string(),
+  };
+  log_error(e);
+  let _ = PLUGIN_RPC.window_show_message(MessageType::ERROR, message);
+}
+
+fn log_error(e: &anyhow::Error) {
+  log::write(log::Level::Error, &e.to_string());
+  let _ = PLUGIN_RPC.window_log_message(MessageType::ERROR, e.to_string());
+}
+
+#[cfg(target_os = "wasi")]
 impl LapcePlugin for State {
   fn handle_request(&mut self, _id: u64, method: String, params: Value) {
-    #[allow(clippy::single_match)]
     match method.as_str() {
       | Initialize::METHOD => {
         let params: InitializeParams = serde_json::from_value(params).unwrap();
-        if let Err(e) = initialize(params) {
-          let _ = PLUGIN_RPC.window_log_message(MessageType::ERROR, e.to_string());
-          let _ = PLUGIN_RPC.window_show_message(MessageType::ERROR, e.to_string());
+        self.pending_initialize = Some(params);
+      }
+      | Shutdown::METHOD => {
+        if let Err(e) = shutdown(self, &Lapce) {
+          log_error(&e);
+        }
+      }
+      | DIAGNOSE_COMMAND => {
+        if let Err(e) = diagnose(self, &Lapce) {
+          report_error(&e);
+        }
+      }
+      | VALIDATE_COMMAND => {
+        let (Some(config), Some(root_uri)) = (&self.config, &self.root_uri) else {
+          let _ = PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            string!("Open a workspace folder to validate it"),
+          );
+          return;
+        };
+        if let Err(e) = validate::validate(&Lapce, config, root_uri) {
+          report_error(&e);
+        }
+      }
+      | INSPECT_LOCK_FILE_COMMAND => {
+        let Some(root_uri) = &self.root_uri else {
+          let _ = PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            format!(
+              "Open a workspace folder to inspect its {}",
+              lockfile::LOCK_FILE
+            ),
+          );
+          return;
+        };
+        if let Err(e) = lockfile::inspect(&Lapce, root_uri) {
+          report_error(&e);
+        }
+      }
+      | OPEN_SERVER_LOG_COMMAND | TAIL_SERVER_LOG_COMMAND => {
+        let tail = method == TAIL_SERVER_LOG_COMMAND;
+        if let Err(e) = serverlog::open(&Lapce, self.server_log.as_deref(), tail) {
+          report_error(&e);
+        }
+      }
+      | PREFETCH_SCHEMAS_COMMAND => {
+        let (Some(config), Some(root_uri)) = (&self.config, &self.root_uri) else {
+          let _ = PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            string!("Open a workspace folder to fetch its provider schemas"),
+          );
+          return;
+        };
+        if let Err(e) = prewarm::prewarm(&Lapce, config, root_uri) {
+          report_error(&e);
+        }
+      }
+      | OPEN_DOCS_COMMAND => {
+        let Some(config) = &self.config else {
+          return;
+        };
+        if let Err(e) = docs::open(
+          &Lapce,
+          config,
+          self.root_uri.as_ref(),
+          params,
+          self.last_document.as_ref(),
+        ) {
+          report_error(&e);
+        }
+      }
+      | MODULE_SOURCE_COMMAND => {
+        let Some(config) = &self.config else {
+          return;
+        };
+        if let Err(e) = modules::go_to_source(&Lapce, config, params, self.last_document.as_ref()) {
+          report_error(&e);
+        }
+      }
+      | VARIABLES_COMMAND => {
+        let Some(root_uri) = &self.root_uri else {
+          let _ = PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            string!("Open a workspace folder to report its variables"),
+          );
+          return;
         };
+        if let Err(e) = variables::report(&Lapce, root_uri) {
+          report_error(&e);
+        }
+      }
+      | ESTIMATE_COSTS_COMMAND => {
+        let (Some(root_uri), Some(config)) = (&self.root_uri, &self.config) else {
+          let _ = PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            string!("Open a workspace folder to estimate its costs"),
+          );
+          return;
+        };
+        let mut infracost = infracost::Infracost::new(config);
+        if let Err(e) = tools::run(&Lapce, &mut self.publisher, &mut infracost, root_uri) {
+          report_error(&e);
+        }
+      }
+      | SELECT_VERSION_COMMAND => {
+        if let Err(e) = select_version(self, &Lapce) {
+          report_error(&e);
+        }
+      }
+      | SNOOZE_UPDATES_COMMAND => {
+        let Some(config) = &self.config else {
+          return;
+        };
+        if let Err(e) = updates::snooze(&Lapce, config.product.name) {
+          report_error(&e);
+        }
+      }
+      | SKIP_UPDATE_COMMAND => {
+        let (Some(config), Some(version)) = (&self.config, &self.available_update) else {
+          let _ = PLUGIN_RPC
+            .window_show_message(MessageType::INFO, string!("There is no update to skip"));
+          return;
+        };
+        if let Err(e) = updates::skip(&Lapce, config.product.name, version) {
+          report_error(&e);
+        }
+      }
+      | REINSTALL_COMMAND => {
+        if let Err(e) = reinstall(self, &Lapce) {
+          report_error(&e);
+        }
+      }
+      | CLEAN_CACHE_COMMAND => {
+        if let Err(e) = clean_cache(self, &Lapce) {
+          report_error(&e);
+        }
+      }
+      | _ => {}
+    }
+  }
+
+  fn handle_notification(&mut self, method: String, params: Value) {
+    if self.shutting_down {
+      return;
+    }
+    for supervisor in &mut self.supervisors {
+      if let Err(e) = supervisor.check(&Lapce) {
+        log_error(&e);
+      }
+    }
+    match self.watcher.poll(&Lapce) {
+      | Ok(true) => {
+        if let Err(e) = project_version_changed(self, &Lapce) {
+          log_error(&e);
+        }
+      }
+      | Ok(false) => {}
+      | Err(e) => log_error(&e),
+    }
+
+    match method.as_str() {
+      | Initialized::METHOD => {
+        let Some(params) = self.pending_initialize.take() else {
+          return;
+        };
+        if let Err(e) = initialize(self, &Lapce, params) {
+          report_error(&e);
+        };
+      }
+      | Exit::METHOD => {
+        if let Err(e) = shutdown(self, &Lapce) {
+          log_error(&e);
+        }
+      }
+      | DidOpenTextDocument::METHOD => {
+        let Ok(params) = serde_json::from_value::<DidOpenTextDocumentParams>(params) else {
+          return;
+        };
+        self.last_document = Some(params.text_document.uri);
+      }
+      | DidSaveTextDocument::METHOD => {
+        let Ok(params) = serde_json::from_value::<DidSaveTextDocumentParams>(params) else {
+          return;
+        };
+        self.last_document = Some(params.text_document.uri.clone());
+        let path = params.text_document.uri.path();
+        if path.ends_with(PROJECT_VERSION_FILE) {
+          if let Err(e) = project_version_changed(self, &Lapce) {
+            log_error(&e);
+          }
+        }
+        if let Some(root_uri) = &self.root_uri {
+          if let Err(e) = self.scanners.scan(
+            &Lapce,
+            &mut self.publisher,
+            root_uri,
+            &params.text_document.uri,
+          ) {
+            log_error(&e);
+          }
+        }
       }
       | _ => {}
     }
@@ -0,0 +1,202 @@
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fs::{self, File, OpenOptions},
+  io::{self, Cursor, Write},
+  path::{Path, PathBuf},
+  sync::atomic::{AtomicUsize, Ordering},
+  time::SystemTime,
+};
+
+use anyhow::Result;
+use psp_types::lsp_types::Url;
+
+use crate::host::{Environment, FileSystem, Http, HttpResponse, Process, ReadSeek};
+
+static VOLT_DIRS: AtomicUsize = AtomicUsize::new(0);
+
+type Program = Box<dyn Fn(&[String]) -> Option<Vec<u8>>>;
+
+// A host for the tests. The volt directory is a temporary directory that is
+// removed again on drop. URLs without a response set up are a 404 and
+// programs without one don't exist.
+pub struct MockHost {
+  pub root: PathBuf,
+  pub os: &'static str,
+  pub arch: &'static str,
+  // Every URL requested, in order.
+  pub requests: RefCell<Vec<String>>,
+  // Every program executed with its arguments, in order.
+  pub executed: RefCell<Vec<(String, Vec<String>)>>,
+  responses: HashMap<String, (u16, Vec<u8>)>,
+  programs: HashMap<String, Program>,
+}
+
+impl MockHost {
+  pub fn new() -> MockHost {
+    let root = std::env::temp_dir().join(format!(
+      "lapce-terraform-ls-{}-{}",
+      std::process::id(),
+      VOLT_DIRS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&root).unwrap();
+    MockHost {
+      root,
+      os: "linux",
+      arch: "x86_64",
+      requests: RefCell::new(vec![]),
+      executed: RefCell::new(vec![]),
+      responses: HashMap::new(),
+      programs: HashMap::new(),
+    }
+  }
+
+  pub fn respond(&mut self, url: &str, status: u16, body: &[u8]) {
+    self.responses.insert(string!(url), (status, body.to_vec()));
+  }
+
+  pub fn program(&mut self, name: &str, f: impl Fn(&[String]) -> Option<Vec<u8>> + 'static) {
+    self.programs.insert(string!(name), Box::new(f));
+  }
+
+  fn path(&self, path: &Path) -> PathBuf {
+    self.root.join(path)
+  }
+}
+
+impl Drop for MockHost {
+  fn drop(&mut self) {
+    let _ = fs::remove_dir_all(&self.root);
+  }
+}
+
+struct MockResponse {
+  status: u16,
+  body: Cursor<Vec<u8>>,
+}
+
+impl HttpResponse for MockResponse {
+  fn status(&self) -> u16 {
+    self.status
+  }
+
+  fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+    Ok(io::Read::read(&mut self.body, buf)?)
+  }
+
+  fn read_all(&mut self) -> Result<Vec<u8>> {
+    let mut body = vec![];
+    io::Read::read_to_end(&mut self.body, &mut body)?;
+    Ok(body)
+  }
+}
+
+impl Http for MockHost {
+  fn get(&self, url: &str) -> Result<Box<dyn HttpResponse>> {
+    self.requests.borrow_mut().push(string!(url));
+    let (status, body) = self.responses.get(url).cloned().unwrap_or((404, vec![]));
+    Ok(Box::new(MockResponse {
+      status,
+      body: Cursor::new(body),
+    }))
+  }
+
+  fn content_length(&self, url: &str) -> Option<u64> {
+    self.requests.borrow_mut().push(format!("HEAD {url}"));
+    let (_, body) = self.responses.get(url)?;
+    Some(body.len() as u64)
+  }
+}
+
+impl Environment for MockHost {
+  fn uri(&self) -> Result<String> {
+    Ok(Url::from_directory_path(&self.root).unwrap().to_string())
+  }
+
+  fn architecture(&self) -> Result<String> {
+    Ok(string!(self.arch))
+  }
+
+  fn operating_system(&self) -> Result<String> {
+    Ok(string!(self.os))
+  }
+}
+
+impl FileSystem for MockHost {
+  fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+    fs::read(self.path(path))
+  }
+
+  fn write(&self, path: &Path, contents: &[u8]) -> io::Result<()> {
+    fs::write(self.path(path), contents)
+  }
+
+  fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+    Ok(Box::new(File::open(self.path(path))?))
+  }
+
+  fn create(&self, path: &Path) -> io::Result<Box<dyn Write>> {
+    Ok(Box::new(File::create(self.path(path))?))
+  }
+
+  fn create_new(&self, path: &Path) -> io::Result<()> {
+    OpenOptions::new()
+      .write(true)
+      .create_new(true)
+      .open(self.path(path))?;
+    Ok(())
+  }
+
+  fn modified(&self, path: &Path) -> io::Result<SystemTime> {
+    fs::metadata(self.path(path))?.modified()
+  }
+
+  // Relative to the volt directory, like the paths it was given.
+  fn read_dir(&self, path: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::read_dir(self.path(path))?
+      .map(|v| Ok(path.join(v?.file_name())))
+      .collect()
+  }
+
+  fn create_dir_all(&self, path: &Path) -> io::Result<()> {
+    fs::create_dir_all(self.path(path))
+  }
+
+  fn remove_file(&self, path: &Path) -> io::Result<()> {
+    fs::remove_file(self.path(path))
+  }
+
+  fn remove_dir_all(&self, path: &Path) -> io::Result<()> {
+    fs::remove_dir_all(self.path(path))
+  }
+
+  fn rename(&self, from: &Path, to: &Path) -> io::Result<()> {
+    fs::rename(self.path(from), self.path(to))
+  }
+
+  fn exists(&self, path: &Path) -> bool {
+    self.path(path).exists()
+  }
+
+  fn is_dir(&self, path: &Path) -> bool {
+    self.path(path).is_dir()
+  }
+
+  fn is_file(&self, path: &Path) -> bool {
+    self.path(path).is_file()
+  }
+}
+
+impl Process for MockHost {
+  fn execute(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>> {
+    self
+      .executed
+      .borrow_mut()
+      .push((string!(program), args.clone()));
+    self.programs.get(program)?(&args)
+  }
+
+  fn output(&self, program: &str, args: Vec<String>) -> Option<Vec<u8>> {
+    self.execute(program, args)
+  }
+}
@@ -0,0 +1,480 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, Result};
+use psp_types::{
+  lsp_types::{
+    request::{ShowDocument, ShowMessageRequest},
+    MessageActionItem, MessageType, ShowDocumentParams, ShowDocumentResult,
+    ShowMessageRequestParams, TextDocumentPositionParams, Url,
+  },
+  Request,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::{
+  config::Config,
+  docs,
+  host::Host,
+  rpc::PLUGIN_RPC,
+  server::{self, host_path, read_host_file, volt_file_uri},
+};
+
+// Where `terraform init` records the installed modules, which is also what
+// the server's `module.calls` command reads.
+const MODULES_MANIFEST: &str = ".terraform/modules/modules.json";
+
+// Every module is a button, so only the first ones are offered.
+const PICKER_LIMIT: usize = 10;
+
+const OPEN_DOWNLOADED: &str = "Open the downloaded copy";
+const OPEN_IN_BROWSER: &str = "Open in the browser";
+const CLONE: &str = "Clone and open";
+
+pub enum ModuleSource {
+  // `./network`, relative to the calling module.
+  Local(String),
+  // `hashicorp/consul/aws` or `app.terraform.io/example/consul/aws`
+  Registry {
+    host: Option<String>,
+    namespace: String,
+    name: String,
+    provider: String,
+  },
+  // `git::https://example.com/vpc.git//modules/subnet?ref=v1.2.0`, or any of
+  // the GitHub and Bitbucket shorthands.
+  Git {
+    url: String,
+    subdir: Option<String>,
+    git_ref: Option<String>,
+  },
+  // HTTP archives, S3 and GCS buckets and Mercurial repositories.
+  Other(String),
+}
+
+impl ModuleSource {
+  pub fn parse(source: &str) -> ModuleSource {
+    if source.starts_with("./") || source.starts_with("../") {
+      return ModuleSource::Local(string!(source));
+    }
+
+    let (address, query) = match source.split_once('?') {
+      | Some((address, query)) => (address, Some(query)),
+      | None => (source, None),
+    };
+    let git_ref = query.and_then(|query| {
+      query
+        .split('&')
+        .find_map(|v| v.strip_prefix("ref="))
+        .map(String::from)
+    });
+    // `//` separates the package from a path within it, after the scheme.
+    let (scheme_len, rest) = match address.find("://") {
+      | Some(i) => (i + 3, &address[i + 3..]),
+      | None => (0, address),
+    };
+    let (package, subdir) = match rest.find("//") {
+      | Some(i) => (&address[..scheme_len + i], Some(string!(&rest[i + 2..]))),
+      | None => (address, None),
+    };
+
+    let git = |url: &str| ModuleSource::Git {
+      url: string!(url),
+      subdir: subdir.clone(),
+      git_ref: git_ref.clone(),
+    };
+    if let Some(url) = package.strip_prefix("git::") {
+      return git(url);
+    }
+    if package.starts_with("git@") {
+      return git(package);
+    }
+    if package.contains("::") || package.contains("://") {
+      return ModuleSource::Other(string!(source));
+    }
+    for shorthand in ["github.com/", "bitbucket.org/"] {
+      if package.starts_with(shorthand) {
+        return git(&format!("https://{}.git", package.trim_end_matches(".git")));
+      }
+    }
+
+    let parts: Vec<&str> = package.split('/').collect();
+    match parts.as_slice() {
+      | [namespace, name, provider] if !namespace.contains('.') => ModuleSource::Registry {
+        host: None,
+        namespace: string!(*namespace),
+        name: string!(*name),
+        provider: string!(*provider),
+      },
+      | [host, namespace, name, provider] if host.contains('.') => ModuleSource::Registry {
+        host: Some(string!(*host)),
+        namespace: string!(*namespace),
+        name: string!(*name),
+        provider: string!(*provider),
+      },
+      | _ => ModuleSource::Other(string!(source)),
+    }
+  }
+}
+
+// A `module` block, with the arguments needed to follow it.
+struct ModuleCall {
+  name: String,
+  source: Option<String>,
+}
+
+fn quoted(v: &str) -> Option<&str> {
+  v.trim().strip_prefix('"')?.strip_suffix('"')
+}
+
+fn module_name(line: &str) -> Option<&str> {
+  let rest = line.trim_start().strip_prefix("module ")?;
+  rest.trim_start().strip_prefix('"')?.split('"').next()
+}
+
+// The `source` of the block starting at `start`, which has to be a literal
+// string for `terraform init` to accept it.
+fn module_call(lines: &[&str], start: usize) -> Option<ModuleCall> {
+  let name = string!(module_name(lines[start])?);
+  let mut depth = 0;
+  let mut source = None;
+  for line in &lines[start..] {
+    if depth == 1 {
+      if let Some((key, value)) = line.split_once('=') {
+        if key.trim() == "source" {
+          source = quoted(value).map(String::from);
+        }
+      }
+    }
+    depth += line.matches('{').count() as i64 - line.matches('}').count() as i64;
+    if depth <= 0 && line.contains('}') {
+      break;
+    }
+  }
+  Some(ModuleCall { name, source })
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ModulesManifest {
+  modules: Vec<InstalledModule>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct InstalledModule {
+  key: String,
+  dir: String,
+}
+
+// The directory `terraform init` downloaded the module to, if it did.
+fn installed_dir(host: &dyn Host, module_dir: &Url, name: &str) -> Option<Url> {
+  let manifest = read_host_file(host, &host_path(&module_dir.join(MODULES_MANIFEST).ok()?))?;
+  let manifest: ModulesManifest = serde_json::from_str(&manifest).ok()?;
+  let installed = manifest.modules.into_iter().find(|v| v.key == name)?;
+  dir_uri(module_dir, &installed.dir)
+}
+
+fn dir_uri(base: &Url, path: &str) -> Option<Url> {
+  base
+    .join(&format!("{}/", path.trim_end_matches(['/', '\\'])))
+    .ok()
+}
+
+// `main.tf` by convention, or the first configuration file otherwise.
+fn entry_file(host: &dyn Host, dir: &Url) -> Option<Url> {
+  let main = dir.join("main.tf").ok()?;
+  if read_host_file(host, &host_path(&main)).is_some() {
+    return Some(main);
+  }
+  let path = host_path(dir);
+  let (program, args) = match server::is_windows(host) {
+    | true => ("cmd", vec![string!("/C"), format!("dir /b \"{path}\"")]),
+    | false => ("ls", vec![path]),
+  };
+  let output = String::from_utf8(host.execute(program, args)?).ok()?;
+  let mut files: Vec<&str> = output
+    .lines()
+    .map(str::trim)
+    .filter(|v| v.ends_with(".tf"))
+    .collect();
+  files.sort_unstable();
+  dir.join(files.first()?).ok()
+}
+
+fn open_dir(host: &dyn Host, dir: &Url) -> Result<()> {
+  let Some(uri) = entry_file(host, dir) else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("There are no .tf files in {}", host_path(dir)),
+    )?;
+    return Ok(());
+  };
+  let result: Result<ShowDocumentResult, _> = PLUGIN_RPC.host_request(
+    ShowDocument::METHOD,
+    ShowDocumentParams {
+      uri: uri.clone(),
+      external: None,
+      take_focus: Some(true),
+      selection: None,
+    },
+  );
+  if !result.is_ok_and(|v| v.success) {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("The module is at {}", host_path(&uri)),
+    )?;
+  }
+  Ok(())
+}
+
+fn registry_url(config: &Config, source: &ModuleSource) -> Option<String> {
+  let ModuleSource::Registry {
+    host,
+    namespace,
+    name,
+    provider,
+  } = source
+  else {
+    return None;
+  };
+  Some(match (host.as_deref(), config.product.name) {
+    | (None | Some("registry.opentofu.org"), "tofu-ls") => {
+      format!("https://search.opentofu.org/module/{namespace}/{name}/{provider}")
+    }
+    | (None | Some("registry.terraform.io"), _) => {
+      format!("https://registry.terraform.io/modules/{namespace}/{name}/{provider}/latest")
+    }
+    | (Some(host), _) => format!("https://{host}/modules/{namespace}/{name}/{provider}"),
+  })
+}
+
+// `git@github.com:org/repo.git` and `ssh://git@github.com/org/repo.git` are
+// browsable as `https://github.com/org/repo`.
+fn web_url(url: &str) -> Option<String> {
+  let url = url.trim_end_matches(".git");
+  if let Some(rest) = url.strip_prefix("git@") {
+    return Some(format!("https://{}", rest.replacen(':', "/", 1)));
+  }
+  if let Some(rest) = url.strip_prefix("ssh://") {
+    let rest = rest.split_once('@').map_or(rest, |(_, v)| v);
+    return Some(format!("https://{rest}"));
+  }
+  url.starts_with("https://").then(|| string!(url))
+}
+
+// Clones are kept in the volt directory and reused on the next lookup.
+fn clone(host: &dyn Host, name: &str, url: &str, git_ref: Option<&str>) -> Result<Url> {
+  let dir: String = format!("{name}-{}", git_ref.unwrap_or("HEAD"))
+    .chars()
+    .map(
+      |c| match c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+        | true => c,
+        | false => '_',
+      },
+    )
+    .collect();
+  let dir = volt_file_uri(host, std::path::Path::new(&format!("modules/{dir}/")))?;
+  if entry_file(host, &dir).is_some() {
+    return Ok(dir);
+  }
+
+  // No URL starts with `-`, such a source can only be meant as a git option.
+  if url.starts_with('-') {
+    return Err(anyhow!("Refusing to clone {url}, it isn't a URL"));
+  }
+  let mut args = vec![string!("clone"), string!("--depth=1")];
+  if let Some(git_ref) = git_ref {
+    args.push(format!("--branch={git_ref}"));
+  }
+  args.push(string!("--"));
+  args.push(string!(url));
+  args.push(host_path(&dir));
+  info!(format!("Cloning {url} to {}", host_path(&dir)));
+  host.execute("git", args).ok_or_else(|| {
+    anyhow!("Failed to clone {url}, check that git is installed and can access it")
+  })?;
+  Ok(dir)
+}
+
+fn choose(message: String, actions: &[&str]) -> Result<Option<String>> {
+  let picked: Option<MessageActionItem> = PLUGIN_RPC.host_request(
+    ShowMessageRequest::METHOD,
+    ShowMessageRequestParams {
+      typ: MessageType::INFO,
+      message,
+      actions: Some(
+        actions
+          .iter()
+          .map(|v| MessageActionItem {
+            title: string!(*v),
+            properties: HashMap::new(),
+          })
+          .collect(),
+      ),
+    },
+  )?;
+  Ok(picked.map(|v| v.title))
+}
+
+// Follows the `source` of the module block under the cursor when the command
+// comes with a position, otherwise offers the modules of the last opened or
+// saved file.
+pub fn go_to_source(
+  host: &dyn Host,
+  config: &Config,
+  params: Value,
+  last_document: Option<&Url>,
+) -> Result<()> {
+  let position = serde_json::from_value::<TextDocumentPositionParams>(params).ok();
+  let Some(document) = position
+    .as_ref()
+    .map(|v| &v.text_document.uri)
+    .or(last_document)
+  else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      string!("Open a Terraform file to follow its modules"),
+    )?;
+    return Ok(());
+  };
+  let path = host_path(document);
+  let contents = read_host_file(host, &path).ok_or_else(|| anyhow!("Failed to read {path}"))?;
+  let lines: Vec<&str> = contents.lines().collect();
+
+  let start = match &position {
+    // The nearest module header at or above the cursor.
+    | Some(position) => {
+      let line = usize::try_from(position.position.line)
+        .unwrap_or(usize::MAX)
+        .min(lines.len().saturating_sub(1));
+      (0..=line).rev().find(|&i| module_name(lines[i]).is_some())
+    }
+    | None => {
+      let calls: Vec<(usize, &str)> = lines
+        .iter()
+        .enumerate()
+        .filter_map(|(i, v)| Some((i, module_name(v)?)))
+        .take(PICKER_LIMIT)
+        .collect();
+      match calls.as_slice() {
+        | [] => None,
+        | [(i, _)] => Some(*i),
+        | _ => {
+          let names: Vec<&str> = calls.iter().map(|(_, v)| *v).collect();
+          let Some(picked) = choose(string!("Go to the source of"), &names)? else {
+            return Ok(());
+          };
+          calls.iter().find(|(_, v)| *v == picked).map(|(i, _)| *i)
+        }
+      }
+    }
+  };
+
+  let Some(call) = start.and_then(|v| module_call(&lines, v)) else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("There is no module block to follow in {path}"),
+    )?;
+    return Ok(());
+  };
+  let Some(source) = call.source else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("Module {} has no literal `source`", call.name),
+    )?;
+    return Ok(());
+  };
+  let module_dir = document.join("./")?;
+
+  match ModuleSource::parse(&source) {
+    | ModuleSource::Local(path) => {
+      let dir = dir_uri(&module_dir, &path).ok_or_else(|| anyhow!("Invalid module path {path}"))?;
+      open_dir(host, &dir)
+    }
+    | registry @ ModuleSource::Registry { .. } => {
+      let url = registry_url(config, &registry)
+        .ok_or_else(|| anyhow!("Invalid registry module {source}"))?;
+      docs::open_url(&url)
+    }
+    | ModuleSource::Git {
+      url,
+      subdir,
+      git_ref,
+    } => {
+      let installed = installed_dir(host, &module_dir, &call.name);
+      let browser = web_url(&url);
+      let mut actions = vec![];
+      if installed.is_some() {
+        actions.push(OPEN_DOWNLOADED);
+      }
+      if browser.is_some() {
+        actions.push(OPEN_IN_BROWSER);
+      }
+      actions.push(CLONE);
+      let picked = choose(format!("Module {} comes from {url}", call.name), &actions)?;
+      match (picked.as_deref(), installed, browser) {
+        | (Some(OPEN_DOWNLOADED), Some(dir), _) => open_dir(host, &dir),
+        | (Some(OPEN_IN_BROWSER), _, Some(browser)) => docs::open_url(&browser),
+        | (Some(CLONE), ..) => {
+          let dir = clone(host, &call.name, &url, git_ref.as_deref())?;
+          let dir = match &subdir {
+            | Some(subdir) => {
+              dir_uri(&dir, subdir).ok_or_else(|| anyhow!("Invalid path {subdir}"))?
+            }
+            | None => dir,
+          };
+          open_dir(host, &dir)
+        }
+        | _ => Ok(()),
+      }
+    }
+    | ModuleSource::Other(source) => {
+      // Archives and buckets can only be followed once downloaded.
+      match installed_dir(host, &module_dir, &call.name) {
+        | Some(dir) => open_dir(host, &dir),
+        | None => {
+          PLUGIN_RPC.window_show_message(
+            MessageType::INFO,
+            format!("Run `{} init` to download {source} first", config.cli()),
+          )?;
+          Ok(())
+        }
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockHost;
+
+  #[test]
+  fn clones_without_taking_the_url_for_an_option() {
+    let mut host = MockHost::new();
+    host.program("git", |_| Some(vec![]));
+    clone(
+      &host,
+      "vpc",
+      "https://github.com/org/vpc.git",
+      Some("v1.0.0"),
+    )
+    .unwrap();
+    let clones = || {
+      host
+        .executed
+        .borrow()
+        .iter()
+        .filter(|(program, _)| program == "git")
+        .map(|(_, args)| args.clone())
+        .collect::<Vec<_>>()
+    };
+    let args = &clones()[0];
+    assert_eq!(args[..4], ["clone", "--depth=1", "--branch=v1.0.0", "--"]);
+    assert_eq!(args[4], "https://github.com/org/vpc.git");
+
+    assert!(clone(&host, "vpc", "--upload-pack=touch /tmp/x", None).is_err());
+    assert_eq!(clones().len(), 1);
+  }
+}
@@ -0,0 +1,117 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{MessageType, Url};
+
+use crate::{
+  cli,
+  config::Config,
+  host::Host,
+  lockfile::LOCK_FILE,
+  log,
+  rpc::PLUGIN_RPC,
+  server::{self, host_path},
+};
+
+// Directories with a committed lock file, which are root modules. Child
+// modules don't have one, initializing those would only create it.
+fn root_modules(host: &dyn Host, root: &str) -> Result<Vec<String>> {
+  let (program, args) = match server::is_windows(host) {
+    | true => (
+      "cmd",
+      vec![
+        string!("/C"),
+        format!("dir /s /b \"{}\\{LOCK_FILE}\"", root.trim_end_matches('\\')),
+      ],
+    ),
+    | false => (
+      "find",
+      vec![
+        string!(root),
+        string!("-name"),
+        string!(LOCK_FILE),
+        string!("-not"),
+        string!("-path"),
+        string!("*/.terraform/*"),
+      ],
+    ),
+  };
+  let output = host
+    .execute(program, args)
+    .ok_or_else(|| anyhow!("Failed to look for {LOCK_FILE} files in {root}"))?;
+  Ok(
+    String::from_utf8_lossy(&output)
+      .lines()
+      .filter_map(|v| {
+        let dir = v.trim().strip_suffix(LOCK_FILE)?;
+        Some(string!(dir.trim_end_matches(['/', '\\'])))
+      })
+      .filter(|v| !v.split(['/', '\\']).any(|v| v == ".terraform"))
+      .collect(),
+  )
+}
+
+// terraform-ls can only load provider schemas for initialized modules, and
+// does so as soon as they are. Initializing up front, without a backend
+// and sticking to the locked versions, saves the wait on first completion.
+pub fn prewarm(host: &dyn Host, config: &Config, root_uri: &Url) -> Result<()> {
+  let cli = cli::cli_path(config);
+  let root = host_path(root_uri);
+  let modules = root_modules(host, &root)?;
+  if modules.is_empty() {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("No root modules with a {LOCK_FILE} were found in {root}"),
+    )?;
+    return Ok(());
+  }
+
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!(
+      "Fetching provider schemas for {} root modules",
+      modules.len()
+    ),
+  )?;
+  let mut failed = vec![];
+  for module in &modules {
+    let (program, args) = server::with_server_env(
+      host,
+      config,
+      &cli,
+      vec![
+        format!("-chdir={module}"),
+        string!("init"),
+        string!("-backend=false"),
+        string!("-input=false"),
+        string!("-lockfile=readonly"),
+        string!("-no-color"),
+      ],
+    );
+    let output = log::timed("schema prefetch", || host.execute(&program, args));
+    match output {
+      | Some(_) => info!(format!("Fetched provider schemas for {module}")),
+      | None => {
+        error!(format!("`{cli} init` failed in {module}"));
+        failed.push(module.as_str());
+      }
+    }
+  }
+
+  let summary = match failed.len() {
+    | 0 => format!(
+      "Fetched provider schemas for {} root modules",
+      modules.len()
+    ),
+    | n => format!(
+      "Fetched provider schemas for {} of {} root modules, `{cli} init` failed in {}",
+      modules.len() - n,
+      modules.len(),
+      failed.join(", ")
+    ),
+  };
+  let kind = match failed.is_empty() {
+    | true => MessageType::INFO,
+    | false => MessageType::WARNING,
+  };
+  PLUGIN_RPC.window_show_message(kind, summary)?;
+  Ok(())
+}
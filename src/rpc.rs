@@ -0,0 +1,55 @@
+#[cfg(not(target_os = "wasi"))]
+pub use detached::PLUGIN_RPC;
+#[cfg(target_os = "wasi")]
+pub use lapce_plugin::PLUGIN_RPC;
+
+// Outside of Lapce, which is only ever the case for the tests, there is no
+// editor on the other end. Messages are dropped and requests fail, like they
+// do when the user dismisses them.
+#[cfg(not(target_os = "wasi"))]
+mod detached {
+  use anyhow::{anyhow, Result};
+  use psp_types::lsp_types::{DocumentSelector, MessageType, Url};
+  use serde::{de::DeserializeOwned, Serialize};
+  use serde_json::Value;
+
+  pub struct Detached;
+
+  pub static PLUGIN_RPC: Detached = Detached;
+
+  impl Detached {
+    pub fn window_log_message(&self, _kind: MessageType, _message: String) -> Result<()> {
+      Ok(())
+    }
+
+    pub fn window_show_message(&self, _kind: MessageType, _message: String) -> Result<()> {
+      Ok(())
+    }
+
+    pub fn start_lsp(
+      &self,
+      _server_uri: Url,
+      _server_args: Vec<String>,
+      _document_selector: DocumentSelector,
+      _options: Option<Value>,
+    ) -> Result<()> {
+      Ok(())
+    }
+
+    pub fn host_request<P: Serialize, D: DeserializeOwned>(
+      &self,
+      method: &str,
+      _params: P,
+    ) -> Result<D> {
+      Err(anyhow!("No editor to answer {method}"))
+    }
+
+    pub fn host_notification<P: Serialize>(&self, _method: &str, _params: P) -> Result<()> {
+      Ok(())
+    }
+
+    pub fn host_success<D: Serialize>(&self, _id: u64, _result: D) -> Result<()> {
+      Ok(())
+    }
+  }
+}
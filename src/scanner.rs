@@ -0,0 +1,199 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::Result;
+use psp_types::{
+  lsp_types::{notification::PublishDiagnostics, Diagnostic, PublishDiagnosticsParams, Url},
+  Notification,
+};
+
+use crate::{
+  checkov::Checkov,
+  config::{Config, HclMode, SecurityScanner},
+  hcl::HclSyntax,
+  host::Host,
+  ignore::IgnoreList,
+  rpc::PLUGIN_RPC,
+  trivy::Trivy,
+};
+
+pub type Findings = BTreeMap<Url, Vec<Diagnostic>>;
+
+pub enum Scanned {
+  Workspace,
+  File(Url),
+}
+
+// An extra tool run on save whose findings are shown as diagnostics.
+pub trait Scanner {
+  fn name(&self) -> &'static str;
+
+  // Whether saving the file at `path` runs the scanner.
+  fn applies_to(&self, path: &str) -> bool {
+    path.ends_with(".tf") || path.ends_with(".tfvars")
+  }
+
+  // Findings of everything that was scanned, replacing the previous findings
+  // within what was scanned.
+  fn scan(&mut self, host: &dyn Host, root_uri: &Url, saved: &Url) -> Result<(Scanned, Findings)>;
+}
+
+struct Entry {
+  scanner: Box<dyn Scanner>,
+  findings: Findings,
+}
+
+// Diagnostics are published per file and replace whatever was published for
+// the file before, so everything the plugin publishes is merged by file
+// first. The findings of each source replace its previous ones.
+#[derive(Default)]
+pub struct Publisher {
+  sources: BTreeMap<&'static str, Findings>,
+  published: BTreeSet<Url>,
+}
+
+impl Publisher {
+  pub fn replace(&mut self, source: &'static str, findings: Findings) -> Result<()> {
+    self.sources.insert(source, findings);
+
+    let mut merged = Findings::new();
+    for findings in self.sources.values() {
+      for (uri, diagnostics) in findings {
+        merged
+          .entry(uri.clone())
+          .or_default()
+          .extend(diagnostics.iter().cloned());
+      }
+    }
+    merged.retain(|_, v| !v.is_empty());
+
+    for uri in &self.published {
+      if !merged.contains_key(uri) {
+        publish(uri.clone(), vec![])?;
+      }
+    }
+    self.published = merged.keys().cloned().collect();
+    for (uri, diagnostics) in merged {
+      publish(uri, diagnostics)?;
+    }
+    Ok(())
+  }
+}
+
+// Findings of the scanners are published as one source.
+const SOURCE: &str = "scanners";
+
+#[derive(Default)]
+pub struct Scanners {
+  entries: Vec<Entry>,
+  ignore: IgnoreList,
+}
+
+impl Scanners {
+  pub fn new(config: &Config) -> Scanners {
+    let mut scanners: Vec<Box<dyn Scanner>> = vec![];
+    if config.security_scanner == Some(SecurityScanner::Trivy) {
+      scanners.push(Box::new(Trivy::new(config)));
+    }
+    if config.checkov {
+      scanners.push(Box::new(Checkov::new(config)));
+    }
+    if !config.hcl_patterns.is_empty() && config.hcl_mode == HclMode::Syntax {
+      scanners.push(Box::new(HclSyntax::new(config)));
+    }
+
+    Scanners {
+      entries: scanners
+        .into_iter()
+        .map(|scanner| Entry {
+          scanner,
+          findings: Findings::new(),
+        })
+        .collect(),
+      ignore: config.diagnostics_ignore.clone(),
+    }
+  }
+
+  // Takes back everything published, before the scanners are replaced.
+  pub fn clear(&mut self, publisher: &mut Publisher) -> Result<()> {
+    publisher.replace(SOURCE, Findings::new())
+  }
+
+  // A failing scanner keeps its previous findings and doesn't stop the others.
+  pub fn scan(
+    &mut self,
+    host: &dyn Host,
+    publisher: &mut Publisher,
+    root_uri: &Url,
+    saved: &Url,
+  ) -> Result<()> {
+    let path = saved.path();
+    if !self.entries.iter().any(|v| v.scanner.applies_to(path)) {
+      return Ok(());
+    }
+
+    for entry in &mut self.entries {
+      if !entry.scanner.applies_to(path) {
+        continue;
+      }
+      match entry.scanner.scan(host, root_uri, saved) {
+        | Ok((Scanned::Workspace, findings)) => entry.findings = findings,
+        | Ok((Scanned::File(uri), findings)) => {
+          entry.findings.remove(&uri);
+          entry.findings.extend(findings);
+        }
+        | Err(e) => error!(format!("{} failed: {e}", entry.scanner.name())),
+      }
+    }
+
+    let mut merged = Findings::new();
+    for entry in &self.entries {
+      for (uri, diagnostics) in &entry.findings {
+        merged.entry(uri.clone()).or_default().extend(
+          diagnostics
+            .iter()
+            .filter(|v| !self.ignore.is_ignored_diagnostic(v))
+            .cloned(),
+        );
+      }
+    }
+    publisher.replace(SOURCE, merged)
+  }
+}
+
+fn publish(uri: Url, diagnostics: Vec<Diagnostic>) -> Result<()> {
+  PLUGIN_RPC.host_notification(
+    PublishDiagnostics::METHOD,
+    PublishDiagnosticsParams::new(uri, diagnostics, None),
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn merges_the_findings_of_all_sources() {
+    let uri = Url::parse("file:///work/main.tf").unwrap();
+    let finding = |message: &str| Diagnostic {
+      message: string!(message),
+      ..Default::default()
+    };
+    let mut publisher = Publisher::default();
+    publisher
+      .replace("trivy", Findings::from([(uri.clone(), vec![finding("a")])]))
+      .unwrap();
+    publisher
+      .replace(
+        "infracost",
+        Findings::from([(uri.clone(), vec![finding("b")])]),
+      )
+      .unwrap();
+    assert!(publisher.published.contains(&uri));
+
+    publisher.replace("trivy", Findings::new()).unwrap();
+    assert!(publisher.published.contains(&uri));
+    publisher.replace("infracost", Findings::new()).unwrap();
+    assert!(publisher.published.is_empty());
+  }
+}
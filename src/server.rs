@@ -0,0 +1,498 @@
+use std::path::Path;
+
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{DocumentFilter, DocumentSelector, Url};
+use serde_json::{Map, Value};
+
+use crate::{
+  config::{Config, HclMode},
+  error::PluginError,
+  host::Host,
+  rpc::PLUGIN_RPC,
+};
+
+pub fn document_selector(config: &Config) -> DocumentSelector {
+  let mut selector = vec![
+    DocumentFilter {
+      language: Some(string!("terraform")),
+      pattern: Some(string!("**/*.tf")),
+      scheme: None,
+    },
+    DocumentFilter {
+      language: Some(string!("terraform")),
+      pattern: Some(string!("**/*.tfvars")),
+      scheme: None,
+    },
+    // Lapce has no language for the lock file, so it is matched by name.
+    DocumentFilter {
+      language: None,
+      pattern: Some(string!("**/.terraform.lock.hcl")),
+      scheme: None,
+    },
+  ];
+  if config.hcl_mode == HclMode::Server {
+    selector.extend(config.hcl_patterns.iter().map(|v| DocumentFilter {
+      language: None,
+      pattern: Some(v.clone()),
+      scheme: None,
+    }));
+  }
+  selector
+}
+
+// The selector of the server for one workspace folder, which only gets the
+// files inside of it.
+pub fn folder_document_selector(config: &Config, folder: &Url) -> DocumentSelector {
+  let folder = host_path(folder).replace('\\', "/");
+  let folder = folder.trim_end_matches('/');
+  document_selector(config)
+    .into_iter()
+    .map(|v| DocumentFilter {
+      pattern: v.pattern.map(|v| format!("{folder}/{v}")),
+      ..v
+    })
+    .collect()
+}
+
+// Lapce initializes every server with the whole workspace, so the server of
+// one workspace folder is kept from indexing the others.
+pub fn folder_options(options: Option<Value>, folders: &[Url], folder: &Url) -> Value {
+  let mut options = match options {
+    | Some(Value::Object(v)) => v,
+    | _ => Map::new(),
+  };
+  let others = folders
+    .iter()
+    .filter(|v| *v != folder)
+    .map(|v| Value::String(host_path(v)));
+  let indexing = options
+    .entry(string!("indexing"))
+    .or_insert_with(|| Value::Object(Map::new()));
+  if let Value::Object(indexing) = indexing {
+    let ignore_paths = indexing
+      .entry(string!("ignorePaths"))
+      .or_insert_with(|| Value::Array(vec![]));
+    if let Value::Array(ignore_paths) = ignore_paths {
+      ignore_paths.extend(others);
+    }
+  }
+  Value::Object(options)
+}
+
+// terraform-ls warns about every file opened without a workspace otherwise.
+// Settings made explicitly are kept.
+pub fn single_file_options(options: Option<Value>) -> Value {
+  let mut options = match options {
+    | Some(Value::Object(v)) => v,
+    | _ => Map::new(),
+  };
+  options
+    .entry(string!("ignoreSingleFileWarning"))
+    .or_insert(Value::Bool(true));
+  Value::Object(options)
+}
+
+// Converts a `file://` URL into a path usable by processes on the host,
+// which don't see the volt directory through the WASI sandbox.
+pub fn host_path(uri: &Url) -> String {
+  let path = uri.path().as_bytes();
+  let mut decoded = Vec::with_capacity(path.len());
+  let mut i = 0;
+  while i < path.len() {
+    let hex = path
+      .get(i + 1..i + 3)
+      .and_then(|v| std::str::from_utf8(v).ok())
+      .and_then(|v| u8::from_str_radix(v, 16).ok());
+    match (path[i], hex) {
+      | (b'%', Some(b)) => {
+        decoded.push(b);
+        i += 3;
+      }
+      | (b, _) => {
+        decoded.push(b);
+        i += 1;
+      }
+    }
+  }
+  let path = String::from_utf8_lossy(&decoded).into_owned();
+  // `/C:/Users/...` on Windows
+  match path.as_bytes() {
+    | [b'/', drive, b':', ..] if drive.is_ascii_alphabetic() => path[1..].replace('/', "\\"),
+    | _ => path,
+  }
+}
+
+pub fn volt_file_uri(host: &dyn Host, path: &Path) -> Result<Url> {
+  let volt_uri = host.uri()?;
+  let path = match path.to_str() {
+    | Some(v) => v,
+    | None => return Err(anyhow!("path.to_str() failed")),
+  };
+  let Ok(volt_uri) = Url::parse(&volt_uri) else {
+    return Err(anyhow!("Failed to parse URL!"));
+  };
+  Ok(volt_uri.join(path)?)
+}
+
+pub fn is_windows(host: &dyn Host) -> bool {
+  matches!(host.operating_system().as_deref(), Ok("windows"))
+}
+
+// Windows limits paths to 260 characters unless they are prefixed with `\\?\`,
+// which the volt directory deep inside `AppData` gets close to.
+const MAX_PATH: usize = 260;
+
+pub fn long_path(host: &dyn Host, path: String) -> String {
+  let is_drive_path = path.as_bytes().get(1) == Some(&b':');
+  match is_windows(host) && is_drive_path && path.len() >= MAX_PATH {
+    | true => format!("\\\\?\\{path}"),
+    | false => path,
+  }
+}
+
+// Zip archives don't reliably carry Unix permissions and the WASI sandbox
+// can't set them, so the mode is set through the host instead.
+pub fn set_executable(host: &dyn Host, server_uri: &Url) -> Result<()> {
+  let path = host_path(server_uri);
+  match host.execute("chmod", vec![string!("0755"), path.clone()]) {
+    | Some(_) => Ok(()),
+    | None => Err(PluginError::NotExecutable { path }.into()),
+  }
+}
+
+pub fn check_executable(host: &dyn Host, server_uri: &Url) -> Result<()> {
+  let path = host_path(server_uri);
+  match host.execute(&long_path(host, path.clone()), vec![string!("version")]) {
+    | Some(_) => Ok(()),
+    | None => Err(PluginError::NotExecutable { path }.into()),
+  }
+}
+
+pub fn can_execute(host: &dyn Host, program: &str) -> bool {
+  host.execute(program, vec![string!("version")]).is_some()
+}
+
+// Runs `command` through the host shell and returns its output.
+fn host_shell(host: &dyn Host, command: &str) -> Option<String> {
+  let (program, args) = match is_windows(host) {
+    | true => ("cmd", vec![string!("/C"), string!(command)]),
+    | false => ("sh", vec![string!("-c"), string!(command)]),
+  };
+  String::from_utf8(host.execute(program, args)?).ok()
+}
+
+// The sandbox has an environment of its own, so it is read via the host.
+pub fn host_env(host: &dyn Host, name: &str) -> Option<String> {
+  let value = match is_windows(host) {
+    | true => host_shell(host, &format!("echo %{name}%"))?,
+    | false => host_shell(host, &format!("printf %s \"${name}\""))?,
+  };
+  let value = value.trim();
+  // cmd leaves unset variables as they are.
+  if value.is_empty() || value == format!("%{name}%") {
+    return None;
+  }
+  Some(string!(value))
+}
+
+fn home_dir(host: &dyn Host) -> Option<String> {
+  match is_windows(host) {
+    | true => host_env(host, "USERPROFILE"),
+    | false => host_env(host, "HOME"),
+  }
+}
+
+// Expands `~` and resolves relative paths against the workspace root. Bare
+// program names are left to be looked up on PATH.
+pub fn resolve_server_path(host: &dyn Host, path: &str, root_uri: Option<&Url>) -> Result<String> {
+  let path = match path.strip_prefix("~/").or_else(|| path.strip_prefix("~\\")) {
+    | Some(rest) => match home_dir(host) {
+      | Some(home) => format!("{home}/{rest}"),
+      | None => {
+        return Err(
+          PluginError::BadServerPath {
+            path: string!(path),
+            reason: string!("failed to resolve the home directory"),
+          }
+          .into(),
+        )
+      }
+    },
+    | None => string!(path),
+  };
+
+  let is_absolute = path.starts_with(['/', '\\']) || path.as_bytes().get(1) == Some(&b':');
+  match root_uri {
+    | Some(root_uri) if !is_absolute && path.contains(['/', '\\']) => Ok(format!(
+      "{}/{path}",
+      host_path(root_uri).trim_end_matches(['/', '\\'])
+    )),
+    | _ => Ok(path),
+  }
+}
+
+pub fn validate_server_path(host: &dyn Host, path: &str) -> Result<()> {
+  if can_execute(host, path) {
+    return Ok(());
+  }
+  if is_windows(host) {
+    return Err(
+      PluginError::BadServerPath {
+        path: string!(path),
+        reason: string!("it does not exist or can't be executed"),
+      }
+      .into(),
+    );
+  }
+
+  let exists = host
+    .execute("test", vec![string!("-e"), string!(path)])
+    .is_some();
+  let reason = match exists {
+    | true => format!("it can't be executed, run `chmod +x {path}` to fix it"),
+    | false => string!("it does not exist"),
+  };
+  Err(
+    PluginError::BadServerPath {
+      path: string!(path),
+      reason,
+    }
+    .into(),
+  )
+}
+
+// Workspace files are outside of the sandbox, so they are read via the host.
+pub fn read_host_file(host: &dyn Host, path: &str) -> Option<String> {
+  let (program, args) = match is_windows(host) {
+    | true => ("cmd", vec![string!("/C"), format!("type \"{path}\"")]),
+    | false => ("cat", vec![string!(path)]),
+  };
+  String::from_utf8(host.execute(program, args)?).ok()
+}
+
+// terraform-ls only sends telemetry to clients that ask for it, which Lapce
+// doesn't, but the Terraform CLI it runs checks in with HashiCorp unless told
+// not to. Variables set in `volt.env` take precedence.
+pub fn server_env(host: &dyn Host, config: &Config) -> Vec<(String, String)> {
+  let mut env = config.env.clone();
+  if !config.telemetry && !is_windows(host) && !env.iter().any(|(k, _)| k == "CHECKPOINT_DISABLE") {
+    env.push((string!("CHECKPOINT_DISABLE"), string!("1")));
+  }
+  // The CLI downloads providers and modules through the same proxy.
+  if let Some(ca_bundle) = &config.ca_bundle_path {
+    if !env.iter().any(|(k, _)| k == "SSL_CERT_FILE") {
+      env.push((string!("SSL_CERT_FILE"), ca_bundle.clone()));
+    }
+  }
+  env
+}
+
+// `program` with `args`, run with the environment of the server. Like the
+// server it runs through `env`, which isn't available on Windows.
+pub fn with_server_env(
+  host: &dyn Host,
+  config: &Config,
+  program: &str,
+  args: Vec<String>,
+) -> (String, Vec<String>) {
+  let env = server_env(host, config);
+  if env.is_empty() || is_windows(host) {
+    return (string!(program), args);
+  }
+  let mut command: Vec<String> = env.iter().map(|(k, v)| format!("{k}={v}")).collect();
+  command.push(string!(program));
+  command.extend(args);
+  (string!("env"), command)
+}
+
+// Everything needed to start the server again after it exited.
+pub struct Launch {
+  pub server_uri: Url,
+  pub server_args: Vec<String>,
+  pub env: Vec<(String, String)>,
+  pub options: Option<Value>,
+  pub document_selector: DocumentSelector,
+  // Serves the language server over TCP on this port, see `TCP_BRIDGE_SCRIPT`.
+  pub debug_port: Option<u16>,
+}
+
+// Runs the server and writes its exit status to the file passed as `$1`.
+// Its stderr is copied to the file passed as `$2`, while still reaching Lapce.
+// Only the end of it is ever read, so every 1000 lines the file is cut down to
+// the last 100 to keep a chatty server from filling the disk.
+const EXIT_STATUS_SCRIPT: &str = "f=$1; e=$2; shift 2; { { \"$@\" 2>&1 1>&3; printf %s \"$?\" > \
+                                  \"$f\"; } | { n=0; while IFS= read -r l || [ -n \"$l\" ]; do \
+                                  printf '%s\\n' \"$l\" >&2; printf '%s\\n' \"$l\" >> \"$e\"; \
+                                  n=$((n + 1)); [ \"$n\" -lt 1000 ] || { tail -n 100 \"$e\" > \
+                                  \"$e.tail\"; mv \"$e.tail\" \"$e\"; n=0; }; done; }; } 3>&1";
+
+// Lapce only talks to servers over stdio, so with a debug port the server
+// runs in the background and `nc` bridges stdio to it, leaving the port open
+// for debuggers and other tools to connect to as well. Runs the server given
+// as `$2` and on, listening on the port passed as `$1`.
+const TCP_BRIDGE_SCRIPT: &str = "port=$1; shift; \"$@\" >/dev/null & server=$!; while ! nc -z \
+                                 127.0.0.1 \"$port\" 2>/dev/null; do kill -0 \"$server\" || exit \
+                                 1; sleep 0.1; done; nc 127.0.0.1 \"$port\"; status=$?; kill \
+                                 \"$server\"; exit $status";
+
+// `start_lsp` can't set environment variables for the server or tell when it
+// exits, so it is started through `env` and `sh` instead when needed. The exit
+// files are where its exit status and stderr are written to. Neither
+// is available on Windows, where the server is always started directly.
+pub fn start_lsp(
+  host: &dyn Host,
+  launch: &Launch,
+  exit_files: Option<(&Path, &Path)>,
+) -> Result<()> {
+  if is_windows(host)
+    || (launch.env.is_empty() && exit_files.is_none() && launch.debug_port.is_none())
+  {
+    if !launch.env.is_empty() {
+      warn!(string!(
+        "`volt.env` is not supported on Windows, set the variables before starting Lapce instead"
+      ));
+    }
+    PLUGIN_RPC.start_lsp(
+      launch.server_uri.clone(),
+      launch.server_args.clone(),
+      launch.document_selector.clone(),
+      launch.options.clone(),
+    )?;
+    return Ok(());
+  }
+
+  let program = match launch.server_uri.scheme() {
+    | "urn" => string!(launch.server_uri.path()),
+    | _ => host_path(&launch.server_uri),
+  };
+  let mut command = vec![];
+  if !launch.env.is_empty() {
+    command.push(string!("env"));
+    command.extend(launch.env.iter().map(|(k, v)| format!("{k}={v}")));
+  }
+  command.push(program);
+  command.extend(launch.server_args.iter().cloned());
+
+  if let Some(port) = launch.debug_port {
+    let mut wrapped = vec![
+      string!("sh"),
+      string!("-c"),
+      string!(TCP_BRIDGE_SCRIPT),
+      string!("sh"),
+      port.to_string(),
+    ];
+    wrapped.append(&mut command);
+    command = wrapped;
+  }
+
+  if let Some((exit_file, stderr_file)) = exit_files {
+    let mut wrapped = vec![
+      string!("sh"),
+      string!("-c"),
+      string!(EXIT_STATUS_SCRIPT),
+      string!("sh"),
+      host_path(&volt_file_uri(host, exit_file)?),
+      host_path(&volt_file_uri(host, stderr_file)?),
+    ];
+    wrapped.append(&mut command);
+    command = wrapped;
+  }
+
+  let args = command.split_off(1);
+  let Ok(program_uri) = Url::parse(&format!("urn:{}", command[0])) else {
+    return Err(anyhow!("Failed to parse URL"));
+  };
+  PLUGIN_RPC.start_lsp(
+    program_uri,
+    args,
+    launch.document_selector.clone(),
+    launch.options.clone(),
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockHost;
+
+  #[test]
+  fn resolves_server_paths() {
+    let mut host = MockHost::new();
+    host.program("sh", |args| match args[1].contains("$HOME") {
+      | true => Some(b"/home/user".to_vec()),
+      | false => None,
+    });
+    let root = Url::parse("file:///work/infra/").unwrap();
+    let resolve = |path| resolve_server_path(&host, path, Some(&root)).unwrap();
+
+    assert_eq!(resolve("/opt/terraform-ls"), "/opt/terraform-ls");
+    assert_eq!(resolve("terraform-ls"), "terraform-ls");
+    assert_eq!(resolve("bin/terraform-ls"), "/work/infra/bin/terraform-ls");
+    assert_eq!(resolve("~/bin/terraform-ls"), "/home/user/bin/terraform-ls");
+    assert_eq!(
+      resolve_server_path(&host, "bin/terraform-ls", None).unwrap(),
+      "bin/terraform-ls"
+    );
+  }
+
+  #[test]
+  fn resolves_windows_paths() {
+    let mut host = MockHost::new();
+    host.os = "windows";
+    host.program("cmd", |args| match args[1].contains("%USERPROFILE%") {
+      | true => Some(b"C:\\Users\\user\r\n".to_vec()),
+      | false => None,
+    });
+    let root = Url::parse("file:///C:/work/").unwrap();
+    let resolve = |path| resolve_server_path(&host, path, Some(&root)).unwrap();
+
+    assert_eq!(
+      resolve("C:\\tools\\terraform-ls.exe"),
+      "C:\\tools\\terraform-ls.exe"
+    );
+    assert_eq!(
+      resolve("bin\\terraform-ls.exe"),
+      "C:\\work/bin\\terraform-ls.exe"
+    );
+    assert_eq!(
+      resolve("~\\bin\\terraform-ls.exe"),
+      "C:\\Users\\user/bin\\terraform-ls.exe"
+    );
+  }
+
+  #[test]
+  fn fails_without_a_home_directory() {
+    let host = MockHost::new();
+    let e = resolve_server_path(&host, "~/bin/terraform-ls", None).unwrap_err();
+    assert!(matches!(
+      e.downcast_ref(),
+      Some(PluginError::BadServerPath { .. })
+    ));
+  }
+
+  #[test]
+  fn runs_the_cli_with_the_server_env() {
+    let mut host = MockHost::new();
+    let config = Config::parse(Some(&serde_json::json!({
+      "volt": { "env": { "TF_LOG": "info" } },
+    })))
+    .unwrap();
+    let (program, args) = with_server_env(&host, &config, "terraform", vec![string!("version")]);
+    assert_eq!(program, "env");
+    assert_eq!(
+      args,
+      [
+        "TF_LOG=info",
+        "CHECKPOINT_DISABLE=1",
+        "terraform",
+        "version"
+      ]
+    );
+
+    host.os = "windows";
+    let (program, args) = with_server_env(&host, &config, "terraform", vec![string!("version")]);
+    assert_eq!(program, "terraform");
+    assert_eq!(args, ["version"]);
+  }
+}
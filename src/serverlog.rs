@@ -0,0 +1,130 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Result};
+use psp_types::{
+  lsp_types::{
+    request::ShowDocument, MessageType, Position, Range, ShowDocumentParams, ShowDocumentResult,
+    Url,
+  },
+  Request,
+};
+
+use crate::{
+  host::Host,
+  rpc::PLUGIN_RPC,
+  server::{self, read_host_file},
+};
+
+// The timestamp is filled in by the plugin so the path is known, the process
+// IDs are only known to the server.
+pub fn log_file(template: &str) -> String {
+  let timestamp = SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|v| v.as_secs())
+    .unwrap_or_default();
+  template.replace("{{.Timestamp}}", &timestamp.to_string())
+}
+
+// `pattern` for `sh`, quoted apart from its `*` wildcards.
+fn sh_glob(pattern: &str) -> String {
+  pattern
+    .split('*')
+    .map(|v| format!("'{}'", v.replace('\'', "'\\''")))
+    .collect::<Vec<_>>()
+    .join("*")
+}
+
+// With `{{.Pid}}` or `{{.Ppid}}` left in the path, the newest matching file
+// is the one of the running server.
+fn resolve(host: &dyn Host, log_file: &str) -> Option<String> {
+  if !log_file.contains("{{") {
+    return Some(string!(log_file));
+  }
+  let pattern = log_file.replace("{{.Pid}}", "*").replace("{{.Ppid}}", "*");
+  let (program, args) = match server::is_windows(host) {
+    | true => (
+      "cmd",
+      vec![
+        string!("/C"),
+        format!("for /f \"delims=\" %f in ('dir /b /s /o-d \"{pattern}\"') do @echo %f"),
+      ],
+    ),
+    | false => (
+      "sh",
+      vec![
+        string!("-c"),
+        format!("ls -t -- {} | head -n 1", sh_glob(&pattern)),
+      ],
+    ),
+  };
+  let output = String::from_utf8(host.execute(program, args)?).ok()?;
+  let path = output.lines().next()?.trim();
+  if path.is_empty() {
+    return None;
+  }
+  Some(string!(path))
+}
+
+// Opens the server log in Lapce, at the end when `tail` is set.
+pub fn open(host: &dyn Host, log_file: Option<&str>, tail: bool) -> Result<()> {
+  let Some(log_file) = log_file else {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      string!("Set `volt.serverLogFile` for the language server to log to a file"),
+    )?;
+    return Ok(());
+  };
+  let Some(path) = resolve(host, log_file) else {
+    return Err(anyhow!("No server log matching {log_file} was found"));
+  };
+  let uri = match server::is_windows(host) {
+    | true => Url::parse(&format!("file:///{}", path.replace('\\', "/"))),
+    | false => Url::parse(&format!("file://{path}")),
+  }
+  .map_err(|e| anyhow!("Failed to open {path}: {e}"))?;
+
+  let selection = match tail {
+    | true => {
+      let lines = read_host_file(host, &path).map_or(0, |v| v.lines().count());
+      let end = Position {
+        line: u32::try_from(lines).unwrap_or(u32::MAX),
+        character: 0,
+      };
+      Some(Range { start: end, end })
+    }
+    | false => None,
+  };
+
+  let result: Result<ShowDocumentResult, _> = PLUGIN_RPC.host_request(
+    ShowDocument::METHOD,
+    ShowDocumentParams {
+      uri,
+      external: Some(false),
+      take_focus: Some(true),
+      selection,
+    },
+  );
+  if !result.is_ok_and(|v| v.success) {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("The language server logs to {path}"),
+    )?;
+  }
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::mock::MockHost;
+
+  #[test]
+  fn quotes_the_log_file_pattern() {
+    let mut host = MockHost::new();
+    host.program("sh", |args| Some(args[1].clone().into_bytes()));
+    assert_eq!(
+      resolve(&host, "/home/o'neil/my logs/terraform-ls-{{.Pid}}.log").as_deref(),
+      Some("ls -t -- '/home/o'\\''neil/my logs/terraform-ls-'*'.log' | head -n 1")
+    );
+  }
+}
@@ -0,0 +1,58 @@
+use std::{
+  collections::{BTreeMap, BTreeSet},
+  path::Path,
+};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::host::Host;
+
+// Outside of the install directories so reinstalling keeps it.
+const STATE_FILE: &str = "state.json";
+
+// Choices made through commands, kept across sessions. Shared by plugin
+// instances in all windows, so it is read right before it's needed and
+// written right after changing it.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistedState {
+  // Picked with the version picker, by product.
+  pub selected_versions: BTreeMap<String, String>,
+  // Until when update notifications are snoozed, in seconds since the epoch,
+  // by product.
+  pub updates_snoozed_until: BTreeMap<String, u64>,
+  // When the releases were last checked for updates, in seconds since the
+  // epoch, by product.
+  pub updates_checked_at: BTreeMap<String, u64>,
+  // Releases not to notify about, by product.
+  pub skipped_updates: BTreeMap<String, Vec<String>>,
+  // Whether the plugin may download releases, asked before the first
+  // download, by product.
+  pub download_consents: BTreeMap<String, bool>,
+  // CLIs found missing or outdated, which was told about already.
+  pub cli_problems_notified: BTreeSet<String>,
+}
+
+impl PersistedState {
+  // A missing or unreadable file is the same as one without choices.
+  pub fn load(host: &dyn Host) -> PersistedState {
+    host
+      .read(Path::new(STATE_FILE))
+      .ok()
+      .and_then(|v| serde_json::from_slice(&v).ok())
+      .unwrap_or_default()
+  }
+
+  pub fn save(&self, host: &dyn Host) -> Result<()> {
+    host.write(Path::new(STATE_FILE), &serde_json::to_vec_pretty(self)?)?;
+    Ok(())
+  }
+
+  // Applies `f` to the current state on disk and saves the result.
+  pub fn update(host: &dyn Host, f: impl FnOnce(&mut PersistedState)) -> Result<()> {
+    let mut state = PersistedState::load(host);
+    f(&mut state);
+    state.save(host)
+  }
+}
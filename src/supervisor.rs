@@ -0,0 +1,244 @@
+use std::{
+  path::{Path, PathBuf},
+  time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use psp_types::lsp_types::MessageType;
+
+use crate::{
+  host::Host,
+  log,
+  rpc::PLUGIN_RPC,
+  server::{self, Launch},
+};
+
+const RESTART_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_RESTART_BACKOFF: Duration = Duration::from_secs(60);
+// Exit files of servers whose plugin instance went away before reading them.
+const STALE_EXIT_FILE: Duration = Duration::from_secs(60 * 60);
+// A server that ran this long before exiting gets a fresh set of restarts.
+const STABLE_RUN: Duration = Duration::from_secs(5 * 60);
+
+// Lines of stderr logged when the server fails to start.
+const STDERR_TAIL_LINES: usize = 20;
+
+struct Running {
+  name: &'static str,
+  launch: Launch,
+  started_at: Instant,
+  exited_at: Option<Instant>,
+  // Set once the server kept running for the init timeout, exits before
+  // that are start up failures that restarting won't fix.
+  up: bool,
+}
+
+// Restarts the server when it exits, or reports why when it exits right after
+// starting. The plugin has no timers, so exits are only noticed, and restarts
+// only happen, while handling messages from the host.
+pub struct Supervisor {
+  // Written by the wrapper the server runs in, named per instance as plugin
+  // instances in other windows share the volt directory.
+  exit_file: PathBuf,
+  stderr_file: PathBuf,
+  running: Option<Running>,
+  restarts: u32,
+  max_restarts: u32,
+  init_timeout: Option<Duration>,
+}
+
+// The wrapper writes the exit files after the server has exited, which is
+// after the plugin shut down when Lapce exits.
+fn remove_stale_exit_files(host: &dyn Host) {
+  let Ok(entries) = host.read_dir(Path::new(".")) else {
+    return;
+  };
+  for path in entries {
+    let is_exit_file = path.file_name().and_then(|v| v.to_str()).is_some_and(|v| {
+      v.starts_with("server-") && (v.ends_with(".exit") || v.ends_with(".stderr"))
+    });
+    let is_stale = host
+      .modified(&path)
+      .ok()
+      .and_then(|v| v.elapsed().ok())
+      .is_some_and(|v| v > STALE_EXIT_FILE);
+    if is_exit_file && is_stale {
+      let _ = host.remove_file(&path);
+    }
+  }
+}
+
+impl Supervisor {
+  // `instance` tells apart the servers of one plugin instance.
+  pub fn new(instance: usize) -> Self {
+    let id = SystemTime::now()
+      .duration_since(UNIX_EPOCH)
+      .map(|v| v.as_nanos())
+      .unwrap_or_default();
+    Supervisor {
+      exit_file: PathBuf::from(format!("server-{id}-{instance}.exit")),
+      stderr_file: PathBuf::from(format!("server-{id}-{instance}.stderr")),
+      running: None,
+      restarts: 0,
+      max_restarts: 0,
+      init_timeout: None,
+    }
+  }
+
+  pub fn start(
+    &mut self,
+    host: &dyn Host,
+    name: &'static str,
+    launch: Launch,
+    max_restarts: u32,
+    init_timeout: Option<Duration>,
+  ) -> Result<()> {
+    self.max_restarts = max_restarts;
+    self.init_timeout = init_timeout;
+    if (max_restarts == 0 && init_timeout.is_none())
+      || matches!(host.operating_system().as_deref(), Ok("windows"))
+    {
+      return server::start_lsp(host, &launch, None);
+    }
+
+    let _ = host.remove_file(&self.exit_file);
+    let _ = host.remove_file(&self.stderr_file);
+    remove_stale_exit_files(host);
+    server::start_lsp(host, &launch, Some((&self.exit_file, &self.stderr_file)))?;
+    self.running = Some(Running {
+      name,
+      launch,
+      started_at: Instant::now(),
+      exited_at: None,
+      up: init_timeout.is_none(),
+    });
+    Ok(())
+  }
+
+  // Logs the end of the server's stderr, which is where it reports why it
+  // failed to start, and returns its last line.
+  fn log_stderr(&self, host: &dyn Host, name: &str) -> Option<String> {
+    let stderr = host.read(&self.stderr_file).ok()?;
+    let stderr = String::from_utf8_lossy(&stderr);
+    let lines: Vec<&str> = stderr.lines().filter(|v| !v.trim().is_empty()).collect();
+    let tail = &lines[lines.len().saturating_sub(STDERR_TAIL_LINES)..];
+    for line in tail {
+      log::write(log::Level::Error, &format!("{name} stderr: {line}"));
+    }
+    tail.last().map(|v| string!(v.trim()))
+  }
+
+  // A server that exits before the init timeout failed to start, which
+  // restarting won't fix.
+  fn failed_to_start(&mut self, host: &dyn Host, name: &str, status: &str) -> Result<()> {
+    self.running = None;
+    let reason = match self.log_stderr(host, name) {
+      | Some(line) => format!(": {line}"),
+      | None => String::new(),
+    };
+    PLUGIN_RPC.window_show_message(
+      MessageType::ERROR,
+      format!(
+        "{name} exited with status {status} while starting{reason}. Check `volt.serverArgs`, \
+         `volt.serverPath` and the log, then reload the plugin to try again"
+      ),
+    )?;
+    Ok(())
+  }
+
+  pub fn check(&mut self, host: &dyn Host) -> Result<()> {
+    let Some(running) = self.running.as_mut() else {
+      return Ok(());
+    };
+
+    // The exit file is only missing while the server is running.
+    let timed_out = self
+      .init_timeout
+      .is_some_and(|v| running.started_at.elapsed() >= v);
+    if !running.up && timed_out && !host.exists(&self.exit_file) {
+      running.up = true;
+      debug!(format!(
+        "{} is up after {}s",
+        running.name,
+        running.started_at.elapsed().as_secs()
+      ));
+    }
+
+    let exited_at = match running.exited_at {
+      | Some(v) => v,
+      | None => {
+        let Ok(status) = host.read(&self.exit_file) else {
+          return Ok(());
+        };
+        let _ = host.remove_file(&self.exit_file);
+        let status = String::from_utf8_lossy(&status).trim().to_owned();
+        let name = running.name;
+        if !running.up {
+          return self.failed_to_start(host, name, &status);
+        }
+        if self.max_restarts == 0 {
+          self.running = None;
+          return Ok(());
+        }
+
+        if running.started_at.elapsed() > STABLE_RUN {
+          self.restarts = 0;
+        }
+        if self.restarts >= self.max_restarts {
+          self.running = None;
+          PLUGIN_RPC.window_show_message(
+            MessageType::ERROR,
+            format!(
+              "{name} exited with status {status} and was restarted {} times already, giving \
+               up. Check the log and reload the plugin to try again",
+              self.restarts
+            ),
+          )?;
+          return Ok(());
+        }
+
+        let now = Instant::now();
+        running.exited_at = Some(now);
+        warn!(format!(
+          "{name} exited with status {status}, restarting it in {}s",
+          self.backoff().as_secs()
+        ));
+        now
+      }
+    };
+
+    if exited_at.elapsed() < self.backoff() {
+      return Ok(());
+    }
+
+    let Some(running) = self.running.take() else {
+      return Ok(());
+    };
+    self.restarts += 1;
+    info!(format!(
+      "Restarting {} ({}/{})",
+      running.name, self.restarts, self.max_restarts
+    ));
+    self.start(
+      host,
+      running.name,
+      running.launch,
+      self.max_restarts,
+      self.init_timeout,
+    )
+  }
+
+  // Forgets about the running server, so it exiting isn't taken for a crash.
+  pub fn stop(&mut self, host: &dyn Host) {
+    if self.running.take().is_some() {
+      let _ = host.remove_file(&self.exit_file);
+      let _ = host.remove_file(&self.stderr_file);
+    }
+  }
+
+  fn backoff(&self) -> Duration {
+    RESTART_BACKOFF
+      .saturating_mul(2u32.saturating_pow(self.restarts))
+      .min(MAX_RESTART_BACKOFF)
+  }
+}
@@ -0,0 +1,118 @@
+use std::path::Path;
+
+use anyhow::Result;
+use psp_types::{
+  lsp_types::{request::ShowDocument, MessageType, ShowDocumentParams, ShowDocumentResult, Url},
+  Request,
+};
+
+use crate::{
+  config::Config,
+  error::PluginError,
+  host::Host,
+  install,
+  rpc::PLUGIN_RPC,
+  scanner::{Findings, Publisher},
+  server::{self, host_path, volt_file_uri},
+};
+
+// What a tool run found, as diagnostics, a report for the user to read, or
+// both.
+#[derive(Default)]
+pub struct Output {
+  pub findings: Findings,
+  pub report: Option<String>,
+}
+
+// A CLI from the Terraform ecosystem run on the workspace through a command,
+// unlike scanners which run on save.
+pub trait Tool {
+  fn name(&self) -> &'static str;
+
+  // Host path of the executable, installing it first for tools the plugin can
+  // download.
+  fn executable(&mut self, host: &dyn Host, root_uri: &Url) -> Result<String>;
+
+  fn run(&mut self, host: &dyn Host, executable: &str, root_uri: &Url) -> Result<Output>;
+}
+
+// Diagnostics of one run replace those of the previous run of the same tool.
+pub fn run(
+  host: &dyn Host,
+  publisher: &mut Publisher,
+  tool: &mut dyn Tool,
+  root_uri: &Url,
+) -> Result<()> {
+  let executable = tool.executable(host, root_uri)?;
+  info!(format!("Running {executable} on {}", host_path(root_uri)));
+  let output = tool.run(host, &executable, root_uri)?;
+  publisher.replace(tool.name(), output.findings)?;
+
+  if let Some(report) = output.report {
+    show_report(host, &format!("{}.md", tool.name()), &report)?;
+  }
+  Ok(())
+}
+
+// Writes `report` to `file` in the plugin directory and opens it. Rewritten
+// on every run, so it always shows the latest report.
+pub fn show_report(host: &dyn Host, file: &str, report: &str) -> Result<()> {
+  host.write(Path::new(file), report.as_bytes())?;
+  let uri = volt_file_uri(host, Path::new(file))?;
+  let result: Result<ShowDocumentResult, _> = PLUGIN_RPC.host_request(
+    ShowDocument::METHOD,
+    ShowDocumentParams {
+      uri: uri.clone(),
+      external: None,
+      take_focus: Some(true),
+      selection: None,
+    },
+  );
+  if !result.is_ok_and(|v| v.success) {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!("The report is at {}", host_path(&uri)),
+    )?;
+  }
+  Ok(())
+}
+
+// `path` as set in `setting`, relative paths are resolved against the
+// workspace.
+pub fn configured_path(
+  host: &dyn Host,
+  path: &str,
+  setting: &'static str,
+  root_uri: &Url,
+) -> Result<String> {
+  let path = server::resolve_server_path(host, path, Some(root_uri))?;
+  if !server::can_execute(host, &path) {
+    return Err(
+      PluginError::InvalidSetting {
+        name: setting,
+        value: path,
+      }
+      .into(),
+    );
+  }
+  Ok(path)
+}
+
+// Downloads the pinned release of `config.product` into the plugin directory,
+// see `Config::for_tool`.
+pub fn install(host: &dyn Host, config: &Config) -> Result<String> {
+  let product = config.product;
+  let (os, arch) = install::platform(host)?.map_err(|reason| PluginError::UnsupportedPlatform {
+    product: product.name,
+    reason,
+  })?;
+  let binary_name = install::binary_name(host, product);
+  let (path, fresh) =
+    install::ensure_installed(host, config, product.version, os, arch, &binary_name)?;
+
+  let uri = volt_file_uri(host, &path)?;
+  if !server::is_windows(host) && fresh {
+    server::set_executable(host, &uri)?;
+  }
+  Ok(host_path(&uri))
+}
@@ -0,0 +1,228 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{
+  CodeDescription, Diagnostic, DiagnosticSeverity, NumberOrString, Position, Range, Url,
+};
+use serde::Deserialize;
+
+use crate::{
+  config::Config,
+  host::Host,
+  install::Product,
+  log,
+  scanner::{Findings, Scanned, Scanner},
+  server::host_path,
+  tools,
+};
+
+pub const TRIVY: Product = Product {
+  name: "trivy",
+  version: "0.50.1",
+  releases_host: "https://github.com/aquasecurity/trivy/releases/download",
+  download_url_template: "{host}/v{version}/trivy_{version}_{os}-{arch}.{ext}",
+  releases_index_template: None,
+  github_releases_api: "https://api.github.com/repos/aquasecurity/trivy/releases",
+  platform_names: trivy_platform_names,
+  archive_extension: trivy_archive_extension,
+  checksums_template: "trivy_{version}_checksums.txt",
+};
+
+fn trivy_platform_names(os: &'static str, arch: &'static str) -> (&'static str, &'static str) {
+  let os = match os {
+    | "linux" => "Linux",
+    | "darwin" => "macOS",
+    | "freebsd" => "FreeBSD",
+    | "openbsd" => "OpenBSD",
+    | v => v,
+  };
+  let arch = match arch {
+    | "amd64" => "64bit",
+    | "386" => "32bit",
+    | "arm64" => "ARM64",
+    | "arm" => "ARM",
+    | v => v,
+  };
+  (os, arch)
+}
+
+fn trivy_archive_extension(os: &'static str) -> &'static str {
+  match os {
+    | "windows" => "zip",
+    | _ => "tar.gz",
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Report {
+  #[serde(default)]
+  results: Vec<ReportResult>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct ReportResult {
+  target: String,
+  #[serde(default)]
+  misconfigurations: Vec<Misconfiguration>,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Misconfiguration {
+  #[serde(rename = "ID")]
+  id: String,
+  title: String,
+  #[serde(default)]
+  message: String,
+  severity: String,
+  #[serde(rename = "PrimaryURL")]
+  primary_url: Option<String>,
+  #[serde(default)]
+  status: String,
+  #[serde(default)]
+  cause_metadata: CauseMetadata,
+}
+
+#[derive(Default, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CauseMetadata {
+  #[serde(default)]
+  start_line: u32,
+  #[serde(default)]
+  end_line: u32,
+}
+
+// Runs `trivy config` over the whole workspace.
+pub struct Trivy {
+  // Settings for installing trivy.
+  config: Config,
+  path: Option<String>,
+  // Host path of the binary, installed on the first scan.
+  binary: Option<String>,
+}
+
+impl Trivy {
+  pub fn new(config: &Config) -> Trivy {
+    Trivy {
+      config: config.for_tool(&TRIVY),
+      path: config.trivy_path.clone(),
+      binary: None,
+    }
+  }
+}
+
+impl Scanner for Trivy {
+  fn name(&self) -> &'static str {
+    TRIVY.name
+  }
+
+  fn scan(&mut self, host: &dyn Host, root_uri: &Url, _saved: &Url) -> Result<(Scanned, Findings)> {
+    let binary = match &self.binary {
+      | Some(v) => v.clone(),
+      | None => {
+        let binary = log::timed("trivy install", || {
+          install_trivy(host, &self.config, self.path.as_deref(), root_uri)
+        })?;
+        self.binary = Some(binary.clone());
+        binary
+      }
+    };
+
+    let root = host_path(root_uri);
+    let output = log::timed("trivy scan", || {
+      host.execute(
+        &binary,
+        vec![
+          string!("config"),
+          string!("--format"),
+          string!("json"),
+          string!("--quiet"),
+          root.clone(),
+        ],
+      )
+    })
+    .ok_or_else(|| anyhow!("Failed to scan {root} with {binary}"))?;
+    let report: Report = serde_json::from_slice(&output)
+      .map_err(|e| anyhow!("Failed to parse the output of {binary}: {e}"))?;
+
+    let findings = translate(root_uri, report);
+    debug!(format!(
+      "trivy reported findings in {} files",
+      findings.len()
+    ));
+    Ok((Scanned::Workspace, findings))
+  }
+}
+
+fn install_trivy(
+  host: &dyn Host,
+  config: &Config,
+  path: Option<&str>,
+  root_uri: &Url,
+) -> Result<String> {
+  match path {
+    | Some(path) => tools::configured_path(host, path, "volt.trivyPath", root_uri),
+    | None => tools::install(host, config),
+  }
+}
+
+fn severity(v: &str) -> DiagnosticSeverity {
+  match v {
+    | "CRITICAL" | "HIGH" => DiagnosticSeverity::ERROR,
+    | "MEDIUM" => DiagnosticSeverity::WARNING,
+    | "LOW" => DiagnosticSeverity::INFORMATION,
+    | _ => DiagnosticSeverity::HINT,
+  }
+}
+
+// Groups failed checks by file. Targets are relative to the scanned
+// directory, lines are 1-based and 0 when a finding isn't tied to a line.
+fn translate(root_uri: &Url, report: Report) -> Findings {
+  let root = root_uri.as_str().trim_end_matches('/');
+  let mut findings = Findings::new();
+
+  for result in report.results {
+    if result.misconfigurations.is_empty() {
+      continue;
+    }
+    let Ok(uri) = Url::parse(&format!("{root}/{}", result.target)) else {
+      continue;
+    };
+
+    for finding in result.misconfigurations {
+      if finding.status != "FAIL" {
+        continue;
+      }
+      let start = finding.cause_metadata.start_line.saturating_sub(1);
+      let end = finding.cause_metadata.end_line.saturating_sub(1).max(start);
+      let message = match finding.message.is_empty() {
+        | true => finding.title,
+        | false => format!("{}: {}", finding.title, finding.message),
+      };
+
+      findings.entry(uri.clone()).or_default().push(Diagnostic {
+        range: Range {
+          start: Position {
+            line: start,
+            character: 0,
+          },
+          end: Position {
+            line: end,
+            character: u32::MAX,
+          },
+        },
+        severity: Some(severity(&finding.severity)),
+        code: Some(NumberOrString::String(finding.id)),
+        code_description: finding
+          .primary_url
+          .and_then(|v| Url::parse(&v).ok())
+          .map(|href| CodeDescription { href }),
+        source: Some(string!("trivy")),
+        message,
+        ..Default::default()
+      });
+    }
+  }
+
+  findings
+}
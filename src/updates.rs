@@ -0,0 +1,146 @@
+use std::{
+  collections::HashMap,
+  time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use anyhow::Result;
+use psp_types::{
+  lsp_types::{
+    request::ShowMessageRequest, MessageActionItem, MessageType, ShowMessageRequestParams,
+  },
+  Request,
+};
+
+use crate::{
+  config::Config,
+  host::Host,
+  install::{self, Version},
+  rpc::PLUGIN_RPC,
+  state::PersistedState,
+};
+
+const SNOOZE: Duration = Duration::from_secs(7 * 24 * 60 * 60);
+// Every start of the plugin checks otherwise, which is every window opened.
+const CHECK_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+// Actions of the update notification.
+const SNOOZE_UPDATES: &str = "Remind me in a week";
+const SKIP_UPDATE: &str = "Skip this version";
+
+fn now() -> u64 {
+  SystemTime::now()
+    .duration_since(UNIX_EPOCH)
+    .map(|v| v.as_secs())
+    .unwrap_or_default()
+}
+
+// Tells the user about a release newer than `current`, unless notifications
+// are snoozed, that release was skipped or there was a check within the last
+// day. Returns the release notified about.
+pub fn check(host: &dyn Host, config: &Config, current: &str) -> Result<Option<String>> {
+  let product = config.product.name;
+  let state = PersistedState::load(host);
+  let is_snoozed = state
+    .updates_snoozed_until
+    .get(product)
+    .is_some_and(|v| now() < *v);
+  let is_checked = state
+    .updates_checked_at
+    .get(product)
+    .is_some_and(|v| now() < v + CHECK_INTERVAL.as_secs());
+  if is_snoozed || is_checked {
+    return Ok(None);
+  }
+
+  let Some(current) = Version::parse(current) else {
+    return Ok(None);
+  };
+  let versions = install::list_versions(host, config)?;
+  PersistedState::update(host, |v| {
+    v.updates_checked_at.insert(string!(product), now());
+  })?;
+  let Some(latest) = versions.into_iter().next() else {
+    return Ok(None);
+  };
+  let is_newer = Version::parse(&latest).is_some_and(|v| v > current);
+  let is_skipped = state
+    .skipped_updates
+    .get(product)
+    .is_some_and(|v| v.contains(&latest));
+  if !is_newer || is_skipped {
+    return Ok(None);
+  }
+
+  let action = |title: &str| MessageActionItem {
+    title: string!(title),
+    properties: HashMap::new(),
+  };
+  // Dismissing the notification is the same as not picking an action.
+  let picked: Result<Option<MessageActionItem>, _> = PLUGIN_RPC.host_request(
+    ShowMessageRequest::METHOD,
+    ShowMessageRequestParams {
+      typ: MessageType::INFO,
+      message: format!(
+        "{product} {latest} is available, {current} is installed. Run \
+         `terraform/selectLanguageServerVersion` to install it"
+      ),
+      actions: Some(vec![action(SNOOZE_UPDATES), action(SKIP_UPDATE)]),
+    },
+  );
+  match picked.ok().flatten().as_ref().map(|v| v.title.as_str()) {
+    | Some(SNOOZE_UPDATES) => snooze(host, product)?,
+    | Some(SKIP_UPDATE) => skip(host, product, &latest)?,
+    | _ => {}
+  }
+  Ok(Some(latest))
+}
+
+pub fn snooze(host: &dyn Host, product: &str) -> Result<()> {
+  PersistedState::update(host, |v| {
+    v.updates_snoozed_until
+      .insert(string!(product), now() + SNOOZE.as_secs());
+  })?;
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!("{product} update notifications are snoozed for a week"),
+  )?;
+  Ok(())
+}
+
+pub fn skip(host: &dyn Host, product: &str, version: &str) -> Result<()> {
+  PersistedState::update(host, |v| {
+    let skipped = v.skipped_updates.entry(string!(product)).or_default();
+    if !skipped.iter().any(|v| v == version) {
+      skipped.push(string!(version));
+    }
+  })?;
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!("You won't be notified about {product} {version} again"),
+  )?;
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use serde_json::json;
+
+  use super::*;
+  use crate::mock::MockHost;
+
+  const INDEX_URL: &str = "https://releases.hashicorp.com/terraform-ls/index.json";
+
+  #[test]
+  fn checks_at_most_once_a_day() {
+    let mut host = MockHost::new();
+    let index = json!({ "versions": { "0.32.7": {}, "0.33.0": {} } });
+    host.respond(INDEX_URL, 200, index.to_string().as_bytes());
+    let config = Config::parse(None).unwrap();
+
+    assert_eq!(
+      check(&host, &config, "0.32.7").unwrap().as_deref(),
+      Some("0.33.0")
+    );
+    assert_eq!(check(&host, &config, "0.32.7").unwrap(), None);
+    assert_eq!(host.requests.borrow().as_slice(), [INDEX_URL]);
+  }
+}
@@ -0,0 +1,114 @@
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{MessageType, Url};
+use serde::Deserialize;
+
+use crate::{
+  cli,
+  config::Config,
+  host::Host,
+  log,
+  rpc::PLUGIN_RPC,
+  server::{self, host_path},
+};
+
+#[derive(Deserialize)]
+struct Output {
+  valid: bool,
+  #[serde(default)]
+  error_count: u32,
+  #[serde(default)]
+  warning_count: u32,
+  #[serde(default)]
+  diagnostics: Vec<ValidateDiagnostic>,
+}
+
+#[derive(Deserialize)]
+struct ValidateDiagnostic {
+  severity: String,
+  summary: String,
+  #[serde(default)]
+  detail: String,
+  range: Option<SourceRange>,
+}
+
+#[derive(Deserialize)]
+struct SourceRange {
+  filename: String,
+  start: SourcePos,
+}
+
+#[derive(Deserialize)]
+struct SourcePos {
+  line: u32,
+  column: u32,
+}
+
+// Runs `validate -json` of the CLI in the workspace root, logs every
+// diagnostic and shows a summary. The CLI exits unsuccessfully when the
+// configuration is invalid, which is still a result to report.
+pub fn validate(host: &dyn Host, config: &Config, root_uri: &Url) -> Result<()> {
+  let cli = config.cli();
+  let path = cli::cli_path(config);
+  let root = host_path(root_uri);
+  let (program, args) = server::with_server_env(
+    host,
+    config,
+    &path,
+    vec![
+      format!("-chdir={root}"),
+      string!("validate"),
+      string!("-json"),
+      string!("-no-color"),
+    ],
+  );
+  let output = log::timed("validate", || host.output(&program, args)).ok_or_else(|| {
+    anyhow!("Failed to run `{path} validate`, check that {cli} is installed or set `{cli}.path`")
+  })?;
+  let output: Output = serde_json::from_slice(&output)
+    .map_err(|e| anyhow!("Failed to parse the output of `{cli} validate -json`: {e}"))?;
+
+  for diagnostic in &output.diagnostics {
+    if config
+      .diagnostics_ignore
+      .is_ignored(None, &diagnostic.summary)
+    {
+      continue;
+    }
+    let location = match &diagnostic.range {
+      | Some(range) => format!(
+        "{}:{}:{}: ",
+        range.filename, range.start.line, range.start.column
+      ),
+      | None => String::new(),
+    };
+    let detail = match diagnostic.detail.is_empty() {
+      | true => String::new(),
+      | false => format!(" {}", diagnostic.detail),
+    };
+    let message = format!(
+      "{location}{}: {}{detail}",
+      diagnostic.severity, diagnostic.summary
+    );
+    match diagnostic.severity.as_str() {
+      | "error" => error!(message),
+      | _ => info!(message),
+    }
+  }
+
+  let (kind, summary) = match (output.valid, output.warning_count) {
+    | (true, 0) => (MessageType::INFO, format!("{root} is valid")),
+    | (true, warnings) => (
+      MessageType::WARNING,
+      format!("{root} is valid with {warnings} warnings, see the log for details"),
+    ),
+    | (false, warnings) => (
+      MessageType::ERROR,
+      format!(
+        "{root} is invalid with {} errors and {warnings} warnings, see the log for details",
+        output.error_count
+      ),
+    ),
+  };
+  PLUGIN_RPC.window_show_message(kind, summary)?;
+  Ok(())
+}
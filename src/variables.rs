@@ -0,0 +1,417 @@
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use psp_types::lsp_types::{MessageType, Url};
+
+use crate::{
+  host::Host,
+  lockfile::LOCK_FILE,
+  rpc::PLUGIN_RPC,
+  server::{self, host_path, read_host_file},
+  tools,
+};
+
+const REPORT_FILE: &str = "variables.md";
+
+struct Declaration {
+  name: String,
+  file: String,
+  line: usize,
+  has_default: bool,
+}
+
+struct Assignment {
+  name: String,
+  file: String,
+  line: usize,
+}
+
+// What a directory, which is one module, declares, references and assigns.
+#[derive(Default)]
+struct Module {
+  declarations: Vec<Declaration>,
+  references: Vec<String>,
+  assignments: Vec<Assignment>,
+  // Only root modules get their values from `.tfvars` files, child modules
+  // get them from the calling `module` block.
+  is_root: bool,
+}
+
+// Every `.tf`, `.tfvars` and lock file of the workspace, except for the
+// modules downloaded by `terraform init`.
+fn workspace_files(host: &dyn Host, root: &str) -> Result<Vec<String>> {
+  let (program, args) = match server::is_windows(host) {
+    | true => {
+      let root = root.trim_end_matches('\\');
+      (
+        "cmd",
+        vec![
+          string!("/C"),
+          format!("dir /s /b \"{root}\\*.tf\" \"{root}\\*.tfvars\" \"{root}\\{LOCK_FILE}\""),
+        ],
+      )
+    }
+    | false => (
+      "find",
+      vec![
+        string!(root),
+        string!("-not"),
+        string!("-path"),
+        string!("*/.terraform/*"),
+        string!("("),
+        string!("-name"),
+        string!("*.tf"),
+        string!("-o"),
+        string!("-name"),
+        string!("*.tfvars"),
+        string!("-o"),
+        string!("-name"),
+        string!(LOCK_FILE),
+        string!(")"),
+      ],
+    ),
+  };
+  let output = host
+    .execute(program, args)
+    .ok_or_else(|| anyhow!("Failed to look for Terraform files in {root}"))?;
+  Ok(
+    String::from_utf8_lossy(&output)
+      .lines()
+      .map(|v| string!(v.trim()))
+      .filter(|v| !v.is_empty() && !v.split(['/', '\\']).any(|v| v == ".terraform"))
+      .collect(),
+  )
+}
+
+fn is_comment(line: &str) -> bool {
+  let line = line.trim_start();
+  line.starts_with('#') || line.starts_with("//")
+}
+
+// Brackets opened minus those closed, outside of strings and comments.
+fn depth_change(line: &str) -> i64 {
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut chars = line.chars().peekable();
+  while let Some(c) = chars.next() {
+    match (in_string, c) {
+      | (true, '\\') => {
+        chars.next();
+      }
+      | (_, '"') => in_string = !in_string,
+      | (true, _) => {}
+      | (false, '#') => break,
+      | (false, '/') if chars.peek() == Some(&'/') => break,
+      | (false, '{' | '[' | '(') => depth += 1,
+      | (false, '}' | ']' | ')') => depth -= 1,
+      | _ => {}
+    }
+  }
+  depth
+}
+
+// `EOT` of a line opening a `<<EOT` or `<<-EOT` heredoc.
+fn heredoc_marker(line: &str) -> Option<String> {
+  let marker = line.rsplit_once("<<")?.1.trim_start_matches('-').trim();
+  let is_marker = !marker.is_empty() && marker.chars().all(|c| c.is_alphanumeric() || c == '_');
+  is_marker.then(|| string!(marker))
+}
+
+// Lines with their index, leaving out comments and the bodies of heredocs.
+fn code_lines(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+  let mut heredoc: Option<String> = None;
+  contents.lines().enumerate().filter(move |(_, line)| {
+    if let Some(marker) = &heredoc {
+      if line.trim() == marker {
+        heredoc = None;
+      }
+      return false;
+    }
+    if is_comment(line) {
+      return false;
+    }
+    heredoc = heredoc_marker(line);
+    true
+  })
+}
+
+fn is_identifier(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+// `variable "region" {` blocks and whether they have a `default`.
+fn parse_declarations(file: &str, contents: &str, declarations: &mut Vec<Declaration>) {
+  let mut current: Option<Declaration> = None;
+  let mut depth = 0;
+  for (i, line) in code_lines(contents) {
+    if depth == 0 {
+      let name = line
+        .trim_start()
+        .strip_prefix("variable ")
+        .and_then(|v| v.trim_start().strip_prefix('"'))
+        .and_then(|v| v.split_once('"'));
+      if let Some((name, rest)) = name {
+        declarations.extend(current.take());
+        // A block on one line, like `variable "a" { default = 1 }`.
+        let has_default = rest
+          .split_once('{')
+          .and_then(|(_, v)| v.split_once('='))
+          .is_some_and(|(key, _)| key.trim() == "default");
+        current = Some(Declaration {
+          name: string!(name),
+          file: string!(file),
+          line: i + 1,
+          has_default,
+        });
+      }
+    }
+    if depth == 1 {
+      if let (Some(current), Some((key, _))) = (current.as_mut(), line.split_once('=')) {
+        current.has_default |= key.trim() == "default";
+      }
+    }
+    depth = (depth + depth_change(line)).max(0);
+    if depth == 0 {
+      declarations.extend(current.take());
+    }
+  }
+  declarations.extend(current);
+}
+
+// Every `var.<name>`, which is the only way to read a variable.
+fn parse_references(contents: &str, references: &mut Vec<String>) {
+  for line in contents.lines().filter(|v| !is_comment(v)) {
+    for (i, _) in line.match_indices("var.") {
+      // Part of a longer name or of an attribute path, like `local.var.x`.
+      if line[..i]
+        .chars()
+        .next_back()
+        .is_some_and(|c| is_identifier(c) || c == '.')
+      {
+        continue;
+      }
+      let name: String = line[i + 4..]
+        .chars()
+        .take_while(|&c| is_identifier(c))
+        .collect();
+      if !name.is_empty() {
+        references.push(name);
+      }
+    }
+  }
+}
+
+// The top level `region = "eu-west-1"` lines of a `.tfvars` file.
+fn parse_assignments(file: &str, contents: &str, assignments: &mut Vec<Assignment>) {
+  let mut depth = 0;
+  for (i, line) in code_lines(contents) {
+    if depth == 0 {
+      if let Some((key, _)) = line.split_once('=') {
+        let key = key.trim();
+        if !key.is_empty() && key.chars().all(is_identifier) {
+          assignments.push(Assignment {
+            name: string!(key),
+            file: string!(file),
+            line: i + 1,
+          });
+        }
+      }
+    }
+    depth = (depth + depth_change(line)).max(0);
+  }
+}
+
+fn file_name(path: &str) -> &str {
+  path.rsplit(['/', '\\']).next().unwrap_or(path)
+}
+
+// Reports, for every module of the workspace, variables that are declared but
+// never read, required ones without a value in any `.tfvars` file of a root
+// module, and values for variables that aren't declared. Plugins can't query
+// the server they started, so the files are parsed instead. The report is
+// written to a file in the plugin directory and opened.
+pub fn report(host: &dyn Host, root_uri: &Url) -> Result<()> {
+  let root = host_path(root_uri);
+  let mut modules: BTreeMap<String, Module> = BTreeMap::new();
+  for path in workspace_files(host, &root)? {
+    let name = file_name(&path);
+    let dir = string!(path[..path.len() - name.len()].trim_end_matches(['/', '\\']));
+    let module = modules.entry(dir).or_default();
+    if name == LOCK_FILE {
+      module.is_root = true;
+      continue;
+    }
+    let Some(contents) = read_host_file(host, &path) else {
+      error!(format!("Failed to read {path}"));
+      continue;
+    };
+    match name.ends_with(".tfvars") {
+      | true => {
+        module.is_root = true;
+        parse_assignments(name, &contents, &mut module.assignments);
+      }
+      | false => {
+        parse_declarations(name, &contents, &mut module.declarations);
+        parse_references(&contents, &mut module.references);
+      }
+    }
+  }
+
+  let mut report = String::from("# Variable usage\n");
+  let (mut unused, mut missing, mut undeclared) = (0, 0, 0);
+  for (dir, module) in &modules {
+    let mut lines = vec![];
+    for v in &module.declarations {
+      if !module.references.contains(&v.name) {
+        unused += 1;
+        lines.push(format!("- Unused: `{}` ({}:{})", v.name, v.file, v.line));
+      }
+      if module.is_root && !v.has_default && !module.assignments.iter().any(|a| a.name == v.name) {
+        missing += 1;
+        lines.push(format!(
+          "- Required but not set in any .tfvars file: `{}` ({}:{})",
+          v.name, v.file, v.line
+        ));
+      }
+    }
+    for a in &module.assignments {
+      if !module.declarations.iter().any(|v| v.name == a.name) {
+        undeclared += 1;
+        lines.push(format!(
+          "- Not declared: `{}` ({}:{})",
+          a.name, a.file, a.line
+        ));
+      }
+    }
+    if lines.is_empty() {
+      continue;
+    }
+    let dir = match dir.strip_prefix(root.trim_end_matches(['/', '\\'])) {
+      | Some("") | None => ".",
+      | Some(v) => v.trim_start_matches(['/', '\\']),
+    };
+    report.push_str(&format!("\n## {dir}\n\n{}\n", lines.join("\n")));
+  }
+
+  if unused + missing + undeclared == 0 {
+    PLUGIN_RPC.window_show_message(
+      MessageType::INFO,
+      format!(
+        "All variables of {} modules are used and set",
+        modules.len()
+      ),
+    )?;
+    return Ok(());
+  }
+  report.push_str(
+    "\nValues passed with `-var`, `TF_VAR_` variables or `.tfvars.json` files are not taken into \
+     account.\n",
+  );
+  PLUGIN_RPC.window_show_message(
+    MessageType::INFO,
+    format!("{unused} unused, {missing} unset and {undeclared} undeclared variables"),
+  )?;
+  tools::show_report(host, REPORT_FILE, &report)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn declarations(contents: &str) -> Vec<(String, bool)> {
+    let mut declarations = vec![];
+    parse_declarations("variables.tf", contents, &mut declarations);
+    declarations
+      .into_iter()
+      .map(|v| (v.name, v.has_default))
+      .collect()
+  }
+
+  #[test]
+  fn parses_declarations() {
+    let contents = r#"
+variable "region" {
+  type = string
+}
+
+variable "tags" {
+  default = {
+    Name = "web"
+  }
+}
+variable "zone" { default = "a" }
+variable "size" { type = number }
+"#;
+    assert_eq!(
+      declarations(contents),
+      [
+        (string!("region"), false),
+        (string!("tags"), true),
+        (string!("zone"), true),
+        (string!("size"), false),
+      ]
+    );
+  }
+
+  #[test]
+  fn ignores_braces_in_strings_and_heredocs() {
+    let contents = r#"
+variable "pattern" {
+  default = "{{.Name}"
+}
+variable "policy" {
+  default = <<EOT
+{
+  "Statement": [
+EOT
+}
+variable "region" {}
+"#;
+    assert_eq!(
+      declarations(contents),
+      [
+        (string!("pattern"), true),
+        (string!("policy"), true),
+        (string!("region"), false),
+      ]
+    );
+  }
+
+  #[test]
+  fn parses_references() {
+    let mut references = vec![];
+    parse_references(
+      r#"
+name = "${var.prefix}-web"
+count = var.enabled ? 1 : 0
+# size = var.commented
+other = local.var.none
+"#,
+      &mut references,
+    );
+    assert_eq!(references, ["prefix", "enabled"]);
+  }
+
+  #[test]
+  fn parses_top_level_assignments() {
+    let mut assignments = vec![];
+    parse_assignments(
+      "terraform.tfvars",
+      r#"
+region = "eu-west-1"
+tags = {
+  Name = "web"
+}
+script = <<EOT
+user = "root"
+EOT
+"#,
+      &mut assignments,
+    );
+    let names: Vec<_> = assignments
+      .iter()
+      .map(|v| (v.name.as_str(), v.line))
+      .collect();
+    assert_eq!(names, [("region", 2), ("tags", 3), ("script", 6)]);
+  }
+}
@@ -0,0 +1,130 @@
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use psp_types::{
+  lsp_types::{
+    notification::DidChangeWatchedFiles, request::RegisterCapability, MessageType, Registration,
+    RegistrationParams, Url,
+  },
+  Notification, Request,
+};
+use serde_json::{json, Value};
+
+use crate::{
+  host::Host,
+  rpc::PLUGIN_RPC,
+  server::{host_path, read_host_file},
+};
+
+const REGISTRATION_ID: &str = "terraform-file-watchers";
+// Changes made outside of the editor, e.g. by `terraform init` in a terminal.
+const WATCHED_GLOBS: &[&str] = &[
+  "**/*.tf",
+  "**/*.tfvars",
+  "**/.terraform.lock.hcl",
+  "**/.terraform/**",
+];
+// Stand-ins for the globs when the host can't watch files, written by
+// `terraform init` whenever providers or modules change.
+const POLLED_FILES: &[&str] = &[".terraform.lock.hcl", ".terraform/modules/modules.json"];
+// Pins the server version, which only the plugin reads, so it is polled
+// whether or not the host watches files. Changes to it are passed back to the
+// caller of `poll`.
+const POLLED_PLUGIN_FILES: &[&str] = &[crate::PROJECT_VERSION_FILE];
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+struct Polling {
+  root_uri: Url,
+  // Whether `POLLED_FILES` are polled, which they are when the host didn't
+  // take the registration.
+  server_files: bool,
+  contents: Vec<Option<String>>,
+  polled_at: Instant,
+}
+
+// Asks the host to watch the files terraform-ls indexes and send changes to
+// it. Lapce may not take the registration, in which case the lock file and
+// module manifest are polled instead. The plugin can't notify the server
+// itself, so a change then only suggests restarting it.
+#[derive(Default)]
+pub struct Watcher {
+  polling: Option<Polling>,
+}
+
+fn read_polled_files(host: &dyn Host, root_uri: &Url, server_files: bool) -> Vec<Option<String>> {
+  let server_files = match server_files {
+    | true => POLLED_FILES,
+    | false => &[],
+  };
+  server_files
+    .iter()
+    .chain(POLLED_PLUGIN_FILES)
+    .map(|v| {
+      let uri = root_uri.join(v).ok()?;
+      read_host_file(host, &host_path(&uri))
+    })
+    .collect()
+}
+
+impl Watcher {
+  pub fn register(&mut self, host: &dyn Host, root_uri: &Url) -> Result<()> {
+    let watchers: Vec<Value> = WATCHED_GLOBS
+      .iter()
+      .map(|v| json!({ "globPattern": v }))
+      .collect();
+    let params = RegistrationParams {
+      registrations: vec![Registration {
+        id: string!(REGISTRATION_ID),
+        method: string!(DidChangeWatchedFiles::METHOD),
+        register_options: Some(json!({ "watchers": watchers })),
+      }],
+    };
+
+    let registered = PLUGIN_RPC.host_request::<_, Value>(RegisterCapability::METHOD, params);
+    match registered {
+      | Ok(_) => debug!(string!("Registered file watchers")),
+      | Err(_) => {
+        debug!(format!(
+          "The host didn't register file watchers, polling {} instead",
+          POLLED_FILES.join(", ")
+        ));
+      }
+    }
+    let server_files = registered.is_err();
+    self.polling = Some(Polling {
+      root_uri: root_uri.clone(),
+      server_files,
+      contents: read_polled_files(host, root_uri, server_files),
+      polled_at: Instant::now(),
+    });
+    Ok(())
+  }
+
+  // The plugin has no timers, so this runs while handling notifications.
+  // Returns whether one of `POLLED_PLUGIN_FILES` changed.
+  pub fn poll(&mut self, host: &dyn Host) -> Result<bool> {
+    let Some(polling) = self.polling.as_mut() else {
+      return Ok(false);
+    };
+    if polling.polled_at.elapsed() < POLL_INTERVAL {
+      return Ok(false);
+    }
+    polling.polled_at = Instant::now();
+
+    let contents = read_polled_files(host, &polling.root_uri, polling.server_files);
+    let split = contents.len() - POLLED_PLUGIN_FILES.len();
+    let server_changed = contents[..split] != polling.contents[..split];
+    let plugin_changed = contents[split..] != polling.contents[split..];
+    polling.contents = contents;
+    if server_changed {
+      PLUGIN_RPC.window_show_message(
+        MessageType::INFO,
+        string!(
+          "Providers or modules changed outside of Lapce, reload the plugin for the language \
+           server to pick them up"
+        ),
+      )?;
+    }
+    Ok(plugin_changed)
+  }
+}